@@ -0,0 +1,62 @@
+// Benchmarks de los caminos calientes: intersección de un rayo, muestreo del
+// cielo y un frame completo de la escena del museo.
+// `cargo bench` (sin criterion: cronometraje simple con `Instant`)
+use nalgebra_glm::Vec3;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use cube::camera::Camera;
+use cube::cube::Cube;
+use cube::material::Material;
+use cube::ray_casting::render_into;
+use cube::ray_intersect::RayIntersect;
+use cube::scene;
+use cube::settings::RenderSettings;
+use cube::skybox::Skybox;
+use cube::sphere::Sphere;
+
+// Repite `f` hasta llenar ~1 s (tras un calentamiento) e imprime el tiempo medio
+fn bench(name: &str, mut f: impl FnMut()) {
+    let warmup = Instant::now();
+    while warmup.elapsed() < Duration::from_millis(200) {
+        f();
+    }
+    let mut iters = 0u64;
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(1) {
+        f();
+        iters += 1;
+    }
+    let per_iter = start.elapsed().as_secs_f64() / iters as f64;
+    let (value, unit) = if per_iter >= 1e-3 { (per_iter * 1e3, "ms") } else { (per_iter * 1e9, "ns") };
+    println!("{name:<24} {value:>10.2} {unit}/iter  ({iters} iter)");
+}
+
+fn main() {
+    let origin = Vec3::new(0.0, 0.0, 5.0);
+    let dir = Vec3::new(0.01, 0.02, -1.0).normalize();
+
+    let sphere = Sphere::new(Vec3::zeros(), 1.0, Material::black());
+    bench("sphere_intersect", || {
+        black_box(sphere.ray_intersect(black_box(&origin), black_box(&dir)));
+    });
+
+    let cube = Cube::new(Vec3::zeros(), 1.0, Material::black());
+    bench("cube_intersect", || {
+        black_box(cube.ray_intersect(black_box(&origin), black_box(&dir)));
+    });
+
+    bench("skybox_sample", || {
+        black_box(Skybox::sample_color(black_box(&dir)));
+    });
+
+    let museum = scene::museum();
+    let camera = Camera::new(Vec3::new(0.0, 2.0, 12.0), Vec3::zeros(), Vec3::new(0.0, 1.0, 0.0));
+    let settings = RenderSettings::default();
+    let (width, height) = (200, 150);
+    let mut buffer = vec![0u32; width * height];
+    bench("museum_frame_200x150", || {
+        render_into(&mut buffer, width, height, &museum.objects, &camera, &museum.lights, &settings);
+        black_box(&buffer);
+    });
+}
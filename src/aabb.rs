@@ -0,0 +1,68 @@
+use nalgebra_glm::Vec3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Aabb { min, max }
+    }
+
+    // Caja vacía: neutra para `union`
+    pub fn empty() -> Self {
+        Aabb::new(Vec3::repeat(f32::INFINITY), Vec3::repeat(f32::NEG_INFINITY))
+    }
+
+    // Caja sin límites: para objetos que no conocen su extensión (nunca se descartan)
+    pub fn infinite() -> Self {
+        Aabb::new(Vec3::repeat(f32::NEG_INFINITY), Vec3::repeat(f32::INFINITY))
+    }
+
+    pub fn from_points(points: &[Vec3]) -> Self {
+        points.iter().fold(Aabb::empty(), |b, p| Aabb::new(b.min.inf(p), b.max.sup(p)))
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(self.min.inf(&other.min), self.max.sup(&other.max))
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.min.iter().chain(self.max.iter()).all(|c| c.is_finite())
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Vec3::new(a.x, a.y, a.z),
+            Vec3::new(b.x, a.y, a.z),
+            Vec3::new(a.x, b.y, a.z),
+            Vec3::new(b.x, b.y, a.z),
+            Vec3::new(a.x, a.y, b.z),
+            Vec3::new(b.x, a.y, b.z),
+            Vec3::new(a.x, b.y, b.z),
+            Vec3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    // true si la caja queda completamente detrás del plano (punto, normal)
+    pub fn is_behind(&self, point: &Vec3, normal: &Vec3) -> bool {
+        self.is_finite() && self.corners().iter().all(|c| (c - point).dot(normal) < 0.0)
+    }
+
+    pub fn contains(&self, point: &Vec3) -> bool {
+        (0..3).all(|i| point[i] >= self.min[i] && point[i] <= self.max[i])
+    }
+
+    // Distancia de `point` a la caja (0 si está dentro)
+    pub fn distance_to(&self, point: &Vec3) -> f32 {
+        let clamped = point.sup(&self.min).inf(&self.max);
+        (point - clamped).magnitude()
+    }
+}
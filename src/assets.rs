@@ -0,0 +1,53 @@
+use once_cell::sync::Lazy;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+// Raíz configurada por el usuario; None = carpeta del ejecutable
+static ASSET_ROOT: Lazy<RwLock<Option<PathBuf>>> = Lazy::new(|| RwLock::new(None));
+
+pub fn set_asset_root(dir: impl Into<PathBuf>) {
+    if let Ok(mut root) = ASSET_ROOT.write() { *root = Some(dir.into()); }
+}
+
+pub fn asset_root() -> Option<PathBuf> {
+    if let Ok(root) = ASSET_ROOT.read() && let Some(dir) = root.as_ref() {
+        return Some(dir.clone());
+    }
+    std::env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf))
+}
+
+// Resuelve una ruta relativa contra la raíz de assets. Si el archivo no existe
+// allí se usa la ruta tal cual (relativa al directorio actual), como antes.
+pub fn resolve(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    if path.is_absolute() { return path.to_path_buf(); }
+    if let Some(root) = asset_root() {
+        let candidate = root.join(path);
+        if candidate.exists() { return candidate; }
+    }
+    path.to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_root_resolves_relative_paths() {
+        let root = std::env::temp_dir().join(format!("cube_assets_{}", std::process::id()));
+        std::fs::create_dir_all(root.join("textures")).unwrap();
+        image::save_buffer(root.join("textures/tile.png"), &[255, 0, 0], 1, 1, image::ColorType::Rgb8).unwrap();
+        set_asset_root(&root);
+
+        assert_eq!(resolve("textures/tile.png"), root.join("textures/tile.png"));
+        // lo que no está bajo la raíz se deja relativo al directorio actual
+        assert_eq!(resolve("textures/missing.png"), PathBuf::from("textures/missing.png"));
+        // las absolutas no se tocan
+        let absolute = std::env::temp_dir().join("elsewhere.png");
+        assert_eq!(resolve(&absolute), absolute);
+        // y las texturas se cargan desde la raíz sin depender del directorio actual
+        assert!(crate::texture::register_image(9001, "textures/tile.png"));
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
@@ -1,21 +1,53 @@
-use nalgebra_glm::Vec3;
+use nalgebra_glm::{self as glm, Vec3};
+use crate::aabb::Aabb;
 use std::f32::consts::PI;
 
+#[derive(Debug, Clone, Copy)]
 pub struct Camera {
     pub position: Vec3, // Camera position in world space
     pub center: Vec3,   // Point the camera is looking at
     pub up: Vec3,      // Up vector
+    pub fov: f32,      // Vertical field of view in radians
+    // FOV that `update_zoom` eases `fov` towards, and how fast (1/s)
+    pub target_fov: f32,
+    pub zoom_speed: f32,
 }
 
+// Limits for `fov_zoom`, in radians
+const MIN_FOV: f32 = 0.05;
+const MAX_FOV: f32 = 2.5;
+
 impl Camera {
     pub fn new(position: Vec3, center: Vec3, up: Vec3) -> Self {
         Camera {
             position,
             center,
             up,
+            fov: PI / 3.0,
+            target_fov: PI / 3.0,
+            zoom_speed: 8.0,
         }
     }
 
+    // Sets the field of view right away (no easing)
+    pub fn with_fov(mut self, fov: f32) -> Self {
+        self.fov = fov.clamp(MIN_FOV, MAX_FOV);
+        self.target_fov = self.fov;
+        self
+    }
+
+    // Lens-style zoom: scales the target FOV (factor < 1 zooms in) without moving
+    // the eye. The actual `fov` follows it in `update_zoom`
+    pub fn fov_zoom(&mut self, factor: f32) {
+        self.target_fov = (self.target_fov * factor).clamp(MIN_FOV, MAX_FOV);
+    }
+
+    // Eases `fov` towards `target_fov`; exponential, so it behaves the same at any frame rate
+    pub fn update_zoom(&mut self, dt: f32) {
+        let t = 1.0 - (-self.zoom_speed.max(0.0) * dt).exp();
+        self.fov += (self.target_fov - self.fov) * t;
+    }
+
     pub fn basis_change(&self, vector: &Vec3) -> Vec3 {
         let forward = (self.center - self.position).normalize();
         let right = forward.cross(&self.up).normalize();
@@ -66,6 +98,34 @@ impl Camera {
         self.position = new_position;
     }
 
+    // Same yaw/pitch conventions as `orbit`, but pivoting around an arbitrary point.
+    // Eye and center are rotated together, so the view turns with the orbit and
+    // whatever sits at the pivot stays in the same place on screen
+    pub fn orbit_around(&mut self, pivot: Vec3, delta_yaw: f32, delta_pitch: f32) {
+        // Yaw: rotation around the world Y axis (positive yaw goes from +X towards +Z)
+        let yaw_axis = Vec3::y();
+        let mut eye = glm::rotate_vec3(&(self.position - pivot), -delta_yaw, &yaw_axis);
+        let mut target = glm::rotate_vec3(&(self.center - pivot), -delta_yaw, &yaw_axis);
+
+        // Pitch: rotation around the camera's right axis (positive pitch lowers the eye).
+        // Skipped if it would bring the view within 0.1 rad of straight up/down
+        let forward = (target - eye).normalize();
+        let right = forward.cross(&self.up);
+        if right.magnitude() > 1e-6 {
+            let right = right.normalize();
+            let pitched_eye = glm::rotate_vec3(&eye, delta_pitch, &right);
+            let pitched_target = glm::rotate_vec3(&target, delta_pitch, &right);
+            let pitched_forward = (pitched_target - pitched_eye).normalize();
+            if pitched_forward.dot(&self.up).abs() < (PI / 2.0 - 0.1).sin() {
+                eye = pitched_eye;
+                target = pitched_target;
+            }
+        }
+
+        self.position = pivot + eye;
+        self.center = pivot + target;
+    }
+
         pub fn zoom(&mut self, delta: f32) {
         let forward = (self.center - self.position).normalize();
         let distance = (self.center - self.position).magnitude();
@@ -74,4 +134,110 @@ impl Camera {
         self.position = self.center - forward * new_distance;
     }
 
-}
\ No newline at end of file
+    // Keeps the current view direction and backs the eye off until the bounding
+    // sphere of the box fits inside the (vertical) field of view
+    pub fn frame_bounds(&mut self, bounds: &Aabb, fov: f32) {
+        if !bounds.is_finite() {
+            return;
+        }
+        let forward = (self.center - self.position).normalize();
+        let radius = (bounds.max - bounds.min).magnitude() * 0.5;
+        let distance = radius / (fov * 0.5).sin();
+
+        self.center = bounds.center();
+        self.position = self.center - forward * distance;
+    }
+
+    // Unlike zoom, which moves the eye towards a fixed center (changing the orbit radius),
+    // dolly translates the eye and the center together along the view axis
+    pub fn dolly(&mut self, distance: f32) {
+        let forward = (self.center - self.position).normalize();
+        self.position += forward * distance;
+        self.center += forward * distance;
+    }
+
+}
+// One recorded camera pose, `time` seconds after the recording started
+#[derive(Debug, Clone, Copy)]
+pub struct CameraKey {
+    pub time: f32,
+    pub position: Vec3,
+    pub center: Vec3,
+    pub fov: f32,
+}
+
+// Camera poses over time, for reproducible fly-throughs. Stored as plain text,
+// one key per line: `time px py pz cx cy cz fov`
+#[derive(Debug, Clone, Default)]
+pub struct CameraPath {
+    pub keys: Vec<CameraKey>,
+}
+
+impl CameraPath {
+    pub fn new() -> Self {
+        CameraPath::default()
+    }
+
+    // Appends the camera's current pose; keys must arrive in increasing time
+    pub fn record(&mut self, time: f32, camera: &Camera) {
+        self.keys.push(CameraKey { time, position: camera.position, center: camera.center, fov: camera.fov });
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map_or(0.0, |key| key.time)
+    }
+
+    // Moves the camera to the pose at `time`, interpolating linearly between the
+    // surrounding keys (clamped to the first/last one). False if the path is empty
+    pub fn apply(&self, time: f32, camera: &mut Camera) -> bool {
+        let (Some(first), Some(last)) = (self.keys.first(), self.keys.last()) else {
+            return false;
+        };
+        let (a, b, t) = match self.keys.iter().position(|key| key.time > time) {
+            Some(0) => (first, first, 0.0),
+            Some(i) => {
+                let (a, b) = (&self.keys[i - 1], &self.keys[i]);
+                (a, b, (time - a.time) / (b.time - a.time).max(1e-6))
+            }
+            None => (last, last, 0.0),
+        };
+        camera.position = glm::lerp(&a.position, &b.position, t);
+        camera.center = glm::lerp(&a.center, &b.center, t);
+        camera.fov = a.fov + (b.fov - a.fov) * t;
+        camera.target_fov = camera.fov;
+        true
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let text: String = self
+            .keys
+            .iter()
+            .map(|k| {
+                format!(
+                    "{} {} {} {} {} {} {} {}\n",
+                    k.time, k.position.x, k.position.y, k.position.z, k.center.x, k.center.y, k.center.z, k.fov
+                )
+            })
+            .collect();
+        std::fs::write(path, text)
+    }
+
+    // The error names the file, or the first malformed line
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        let keys = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                let malformed = || format!("{path}:{}: expected 8 numbers, got \"{line}\"", i + 1);
+                let v: Vec<f32> = line.split_whitespace().map(|s| s.parse().ok()).collect::<Option<_>>().ok_or_else(malformed)?;
+                let [time, px, py, pz, cx, cy, cz, fov] = v[..] else {
+                    return Err(malformed());
+                };
+                Ok(CameraKey { time, position: Vec3::new(px, py, pz), center: Vec3::new(cx, cy, cz), fov })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(CameraPath { keys })
+    }
+}
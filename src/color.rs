@@ -1,3 +1,9 @@
+// Contrato de espacio de color: `Color` guarda siempre valores lineales en
+// escala 0-255 (pueden superar 255 en HDR), también los escritos a mano en
+// materiales, texturas y luces; un color de paleta (sRGB) se convierte con
+// `from_srgb`. Las imágenes de 8 bits se decodifican al leerlas, la iluminación
+// y las mezclas se hacen en lineal y la codificación sRGB solo se aplica a la
+// salida (`to_srgb`, ver `RenderSettings::srgb_output`).
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color {
     pub r: f32,
@@ -10,10 +16,79 @@ impl Color {
         Self { r, g, b }
     }
 
-    pub fn to_hex(&self) -> u32 {
-        ((self.r.clamp(0.0, 255.0) as u32) << 16)
-            | ((self.g.clamp(0.0, 255.0) as u32) << 8)
-            | (self.b.clamp(0.0, 255.0) as u32)
+    // Producto por componentes, tratando `other` como filtro (255 = sin cambio)
+    pub fn modulate(&self, other: &Color) -> Color {
+        Color::new(
+            self.r * other.r / 255.0,
+            self.g * other.g / 255.0,
+            self.b * other.b / 255.0,
+        )
+    }
+
+    // Recorta cada canal al rango mostrable [0, 255]
+    pub fn saturate(&self) -> Color {
+        Color::new(
+            saturate_channel(self.r),
+            saturate_channel(self.g),
+            saturate_channel(self.b),
+        )
+    }
+
+    pub fn to_hex(self) -> u32 {
+        let [r, g, b] = self.to_rgb8();
+        ((r as u32) << 16) | ((g as u32) << 8) | (b as u32)
+    }
+
+    // Inverso de `to_hex` (0xRRGGBB); no decodifica sRGB
+    pub fn from_hex(hex: u32) -> Self {
+        Color::new(
+            ((hex >> 16) & 0xFF) as f32,
+            ((hex >> 8) & 0xFF) as f32,
+            (hex & 0xFF) as f32,
+        )
+    }
+
+    // Bytes recortados a 0-255 para exportar con `image` (sin codificar a sRGB)
+    pub fn to_rgb8(&self) -> [u8; 3] {
+        let c = self.saturate();
+        [c.r as u8, c.g as u8, c.b as u8]
+    }
+
+    pub fn to_rgba8(&self) -> [u8; 4] {
+        let [r, g, b] = self.to_rgb8();
+        [r, g, b, 255]
+    }
+
+    // Media precisión, en la misma escala 0-255 y sin recortar: error relativo
+    // < 0.05 %, de sobra para mostrar; por encima de 65504 satura a infinito
+    pub fn to_f16(&self) -> [f16; 3] {
+        [f16::from_f32(self.r), f16::from_f32(self.g), f16::from_f32(self.b)]
+    }
+
+    pub fn from_f16(c: [f16; 3]) -> Color {
+        Color::new(c[0].to_f32(), c[1].to_f32(), c[2].to_f32())
+    }
+
+    // Interpolación en Oklab: degradados perceptualmente más uniformes que `blend`
+    pub fn blend_oklab(self, other: Color, factor: f32) -> Color {
+        let f = factor.clamp(0.0, 1.0);
+        let a = linear_to_oklab(self);
+        let b = linear_to_oklab(other);
+        oklab_to_linear([
+            a[0] * (1.0 - f) + b[0] * f,
+            a[1] * (1.0 - f) + b[1] * f,
+            a[2] * (1.0 - f) + b[2] * f,
+        ])
+    }
+
+    // Construye un color lineal a partir de componentes sRGB (0-255)
+    pub fn from_srgb(r: f32, g: f32, b: f32) -> Self {
+        Color::new(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b))
+    }
+
+    // Codifica un color lineal (0-255) a sRGB para mostrarlo
+    pub fn to_srgb(self) -> Color {
+        Color::new(linear_to_srgb(self.r), linear_to_srgb(self.g), linear_to_srgb(self.b))
     }
 
     pub fn black() -> Self {
@@ -30,7 +105,49 @@ impl Color {
     }
 }
 
+// NaN se trata como 0 para no propagar basura al framebuffer
+fn saturate_channel(c: f32) -> f32 {
+    if c.is_nan() { 0.0 } else { c.clamp(0.0, 255.0) }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    let c = (c / 255.0).max(0.0);
+    let l = if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) };
+    l * 255.0
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    let c = (c / 255.0).max(0.0);
+    let s = if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+    s * 255.0
+}
+
+// Conversión de Björn Ottosson, sobre RGB lineal normalizado a 0-1
+fn linear_to_oklab(c: Color) -> [f32; 3] {
+    let (r, g, b) = (c.r / 255.0, c.g / 255.0, c.b / 255.0);
+    let l = (0.41222146 * r + 0.53633255 * g + 0.051445995 * b).cbrt();
+    let m = (0.2119035 * r + 0.6806995 * g + 0.10739696 * b).cbrt();
+    let s = (0.08830246 * r + 0.28171885 * g + 0.6299787 * b).cbrt();
+    [
+        0.21045426 * l + 0.7936178 * m - 0.004072047 * s,
+        1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+        0.025904037 * l + 0.78277177 * m - 0.80867577 * s,
+    ]
+}
+
+fn oklab_to_linear(lab: [f32; 3]) -> Color {
+    let l = (lab[0] + 0.39633778 * lab[1] + 0.21580376 * lab[2]).powi(3);
+    let m = (lab[0] - 0.105561346 * lab[1] - 0.06385417 * lab[2]).powi(3);
+    let s = (lab[0] - 0.08948418 * lab[1] - 1.2914855 * lab[2]).powi(3);
+    Color::new(
+        (4.0767417 * l - 3.3077116 * m + 0.23096994 * s) * 255.0,
+        (-1.268438 * l + 2.6097574 * m - 0.34131938 * s) * 255.0,
+        (-0.0041960864 * l - 0.7034186 * m + 1.7076147 * s) * 255.0,
+    )
+}
+
 use std::ops::{Add, Mul};
+use half::f16;
 
 impl Add for Color {
     type Output = Color;
@@ -54,3 +171,56 @@ impl Mul<f32> for Color {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saturate_clamps_under_range_to_zero() {
+        let c = Color::new(-10.0, -0.5, 0.0).saturate();
+        assert_eq!((c.r, c.g, c.b), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn saturate_clamps_over_range_to_255() {
+        let c = Color::new(300.0, 255.5, 1e9).saturate();
+        assert_eq!((c.r, c.g, c.b), (255.0, 255.0, 255.0));
+    }
+
+    #[test]
+    fn saturate_keeps_in_range_and_zeroes_nan() {
+        let c = Color::new(12.5, f32::NAN, 254.0).saturate();
+        assert_eq!((c.r, c.g, c.b), (12.5, 0.0, 254.0));
+    }
+
+    #[test]
+    fn hex_round_trips_through_rgb8() {
+        for hex in [0x000000, 0xFFFFFF, 0x123456, 0xFF8000, 0x00FF7F] {
+            let c = Color::from_hex(hex);
+            assert_eq!(c.to_hex(), hex);
+            let [r, g, b] = c.to_rgb8();
+            assert_eq!(((r as u32) << 16) | ((g as u32) << 8) | b as u32, hex);
+            assert_eq!(c.to_rgba8(), [r, g, b, 255]);
+        }
+    }
+
+    #[test]
+    fn rgb8_clamps_out_of_range() {
+        assert_eq!(Color::new(-5.0, 128.0, 400.0).to_rgb8(), [0, 128, 255]);
+    }
+
+    // Para mostrar el f16 basta: los enteros de 0-255 son exactos, así que el
+    // hex no cambia, y el error relativo del resto (también HDR) es < 0.05 %
+    #[test]
+    fn f16_round_trip_is_precise_enough_for_display() {
+        for v in 0..=255 {
+            let c = Color::new(v as f32, 255.0 - v as f32, v as f32);
+            assert_eq!(Color::from_f16(c.to_f16()).to_hex(), c.to_hex());
+        }
+        for v in [0.37, 12.34, 99.99, 200.5, 254.9, 1234.5, 40000.0] {
+            let back = Color::from_f16(Color::new(v, v, v).to_f16()).r;
+            assert!((back - v).abs() / v < 5e-4, "{v} -> {back}");
+        }
+    }
+}
@@ -1,16 +1,48 @@
 use nalgebra_glm::Vec3;
-use crate::ray_intersect::{RayIntersect, Intersect};
+use crate::aabb::Aabb;
+use crate::ray_intersect::{RayIntersect, Intersect, PrimitiveKind};
 use crate::material::Material;
+use std::sync::Arc;
 
 pub struct Cube {
     pub center: Vec3,
     pub size: f32,
-    pub material: Material,
+    // Compartido: varias primitivas pueden usar el mismo material sin copiarlo
+    pub material: Arc<Material>,
+    // Material por cara en el orden +X, -X, +Y, -Y, +Z, -Z; las caras que
+    // falten (o todas, si está vacío) usan `material`
+    pub face_materials: Vec<Arc<Material>>,
+}
+
+impl Cube {
+    pub fn new(center: Vec3, size: f32, material: impl Into<Arc<Material>>) -> Self {
+        Cube { center, size, material: material.into(), face_materials: Vec::new() }
+    }
+
+    // Hasta 6 materiales, uno por cara (dados, cajas, carteles...)
+    pub fn with_face_materials<M: Into<Arc<Material>>>(mut self, materials: impl IntoIterator<Item = M>) -> Self {
+        self.face_materials = materials.into_iter().take(6).map(Into::into).collect();
+        self
+    }
+
+    fn face_material(&self, normal: &Vec3) -> &Material {
+        let face = if normal.x != 0.0 {
+            if normal.x > 0.0 { 0 } else { 1 }
+        } else if normal.y != 0.0 {
+            if normal.y > 0.0 { 2 } else { 3 }
+        } else if normal.z > 0.0 { 4 } else { 5 };
+        self.face_materials.get(face).unwrap_or(&self.material)
+    }
 }
 
 impl RayIntersect for Cube {
-    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect {
-        let half_size = self.size / 2.0;
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect<'_> {
+        // un tamaño negativo se interpreta por su valor absoluto; un cubo de tamaño 0 no existe
+        let size = self.size.abs();
+        if size == 0.0 || !size.is_finite() {
+            return Intersect::empty();
+        }
+        let half_size = size / 2.0;
         let min = self.center - Vec3::new(half_size, half_size, half_size);
         let max = self.center + Vec3::new(half_size, half_size, half_size);
 
@@ -68,6 +100,66 @@ impl RayIntersect for Cube {
             (((local_point.x + half) / (2.0 * half)), ((local_point.y + half) / (2.0 * half)))
         };
 
-        Intersect::new(point, normal, t, self.material).with_uv(u, v)
+        // tangente: eje del que sale U en cada cara
+        let tangent = if normal.x.abs() > 0.0 { Vec3::z() } else { Vec3::x() };
+
+        Intersect::new(point, normal, t, self.face_material(&normal)).with_uv(u, v).with_tangent(tangent).with_face(ray_direction)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let half = Vec3::repeat(self.size.abs() / 2.0);
+        Aabb::new(self.center - half, self.center + half)
     }
-}
\ No newline at end of file
+
+    fn kind(&self) -> PrimitiveKind {
+        PrimitiveKind::Cube
+    }
+
+    fn edge_distance(&self, point: &Vec3) -> Option<(f32, Vec3)> {
+        let half = self.size.abs() / 2.0;
+        let local = point - self.center;
+        // el eje de la cara es el de mayor coordenada; las aristas están en los otros dos
+        let face_axis = (0..3).max_by(|&a, &b| local[a].abs().total_cmp(&local[b].abs()))?;
+        (0..3)
+            .filter(|&axis| axis != face_axis)
+            .map(|axis| {
+                let mut outward = Vec3::zeros();
+                outward[axis] = local[axis].signum();
+                ((half - local[axis].abs()).max(0.0), outward)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+    }
+
+    fn material(&self) -> Material {
+        (*self.material).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube(size: f32) -> Cube {
+        Cube::new(Vec3::zeros(), size, Material::black())
+    }
+
+    fn hit(cube: &Cube) -> Intersect<'_> {
+        cube.ray_intersect(&Vec3::new(0.0, 0.0, 5.0), &-Vec3::z())
+    }
+
+    #[test]
+    fn zero_size_cube_is_never_hit() {
+        assert!(!hit(&cube(0.0)).is_intersecting);
+        assert!(!hit(&cube(f32::NAN)).is_intersecting);
+    }
+
+    #[test]
+    fn negative_size_behaves_like_its_absolute_value() {
+        let (neg, pos) = (cube(-2.0), cube(2.0));
+        let (neg_hit, pos_hit) = (hit(&neg), hit(&pos));
+        assert!(neg_hit.is_intersecting);
+        assert_eq!(neg_hit.distance, pos_hit.distance);
+        assert_eq!(neg_hit.normal, pos_hit.normal);
+        assert_eq!(neg.bounding_box().min, pos.bounding_box().min);
+    }
+}
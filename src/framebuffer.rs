@@ -1,9 +1,22 @@
 // framebuffer.rs
 
+use crate::color::Color;
+use crate::font::{glyph, GLYPH_WIDTH};
+use half::f16;
+use image::{DynamicImage, Rgb32FImage};
+use std::borrow::Cow;
+
 pub struct Framebuffer {
     pub width: usize,
     pub height: usize,
     pub buffer: Vec<u32>,
+    // Colores lineales sin recortar (escala 0-255 de `Color`), solo si se activa
+    pub hdr: Option<Vec<Color>>,
+    // Alternativa a `hdr` en media precisión (ver `Color::to_f16`): la mitad de
+    // memoria para renders muy grandes. Solo se usa uno de los dos
+    pub hdr_half: Option<Vec<[f16; 3]>>,
+    // Objeto visto en cada píxel (índice en la escena + 1, 0 = fondo), solo si se activa
+    pub ids: Option<Vec<u32>>,
     background_color: u32,
     current_color: u32,
 }
@@ -14,11 +27,79 @@ impl Framebuffer {
             width,
             height,
             buffer: vec![0; width * height],
+            hdr: None,
+            hdr_half: None,
+            ids: None,
+            background_color: 0x000000,
+            current_color: 0xFFFFFF,
+        }
+    }
+
+    // Envuelve un buffer ya existente (0xRRGGBB, fila a fila) sin copiarlo
+    pub fn from_buffer(width: usize, height: usize, buffer: Vec<u32>) -> Self {
+        assert_eq!(buffer.len(), width * height, "el buffer no coincide con width * height");
+        Framebuffer {
+            width,
+            height,
+            buffer,
+            hdr: None,
+            hdr_half: None,
+            ids: None,
             background_color: 0x000000,
             current_color: 0xFFFFFF,
         }
     }
 
+    // Devuelve el buffer de píxeles, p. ej. para devolverlo a quien lo prestó
+    pub fn into_buffer(self) -> Vec<u32> {
+        self.buffer
+    }
+
+    // Guarda además los colores en flotante en cada render (para `save_hdr`)
+    pub fn enable_hdr(&mut self) {
+        self.hdr_half = None;
+        if self.hdr.is_none() {
+            self.hdr = Some(vec![Color::black(); self.width * self.height]);
+        }
+    }
+
+    // Como `enable_hdr`, pero guardando cada canal en f16; reemplaza al buffer f32
+    pub fn enable_hdr_half(&mut self) {
+        self.hdr = None;
+        if self.hdr_half.is_none() {
+            self.hdr_half = Some(vec![Color::black().to_f16(); self.width * self.height]);
+        }
+    }
+
+    // Colores del buffer flotante, sea cual sea su precisión (el de f16 se convierte)
+    pub fn hdr_colors(&self) -> Option<Cow<'_, [Color]>> {
+        match (&self.hdr, &self.hdr_half) {
+            (Some(hdr), _) => Some(Cow::Borrowed(hdr)),
+            (None, Some(half)) => Some(Cow::Owned(half.iter().map(|&c| Color::from_f16(c)).collect())),
+            (None, None) => None,
+        }
+    }
+
+    // Guarda además el id del objeto de cada píxel en cada render (máscaras, composición)
+    pub fn enable_ids(&mut self) {
+        if self.ids.is_none() {
+            self.ids = Some(vec![0; self.width * self.height]);
+        }
+    }
+
+    // Escribe el buffer flotante como OpenEXR (ruta .exr), con 1.0 = 255 de
+    // `Color`. Falla si el buffer flotante no está activado
+    pub fn save_hdr(&self, path: &str) -> bool {
+        let Some(hdr) = self.hdr_colors() else {
+            return false;
+        };
+        let data: Vec<f32> = hdr.iter().flat_map(|c| [c.r / 255.0, c.g / 255.0, c.b / 255.0]).collect();
+        match Rgb32FImage::from_raw(self.width as u32, self.height as u32, data) {
+            Some(img) => DynamicImage::ImageRgb32F(img).save(path).is_ok(),
+            None => false,
+        }
+    }
+
     pub fn clear(&mut self) {
         for pixel in self.buffer.iter_mut() {
             *pixel = self.background_color;
@@ -38,4 +119,36 @@ impl Framebuffer {
     pub fn set_current_color(&mut self, color: u32) {
         self.current_color = color;
     }
-}
\ No newline at end of file
+
+    // Mezcla `top` encima de este framebuffer: alpha 1 deja `top`, 0 deja el
+    // píxel actual. La máscara tiene un valor por píxel, en el mismo orden
+    pub fn composite_over(&mut self, top: &Framebuffer, alpha_mask: &[f32]) {
+        assert_eq!((self.width, self.height), (top.width, top.height), "los framebuffers tienen tamaños distintos");
+        assert_eq!(alpha_mask.len(), self.buffer.len(), "la máscara no coincide con width * height");
+
+        for ((dst, &src), &alpha) in self.buffer.iter_mut().zip(&top.buffer).zip(alpha_mask) {
+            let a = alpha.clamp(0.0, 1.0);
+            let mix = |shift: u32| {
+                let d = ((*dst >> shift) & 0xFF) as f32;
+                let s = ((src >> shift) & 0xFF) as f32;
+                ((d + (s - d) * a).round() as u32) << shift
+            };
+            *dst = mix(16) | mix(8) | mix(0);
+        }
+    }
+
+    // Texto con la fuente 5x7 integrada; cada carácter avanza 6 píxeles
+    pub fn draw_text(&mut self, x: usize, y: usize, text: &str, color: u32) {
+        self.set_current_color(color);
+        for (i, c) in text.chars().enumerate() {
+            let gx = x + i * (GLYPH_WIDTH + 1);
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - col)) != 0 {
+                        self.point(gx + col, y + row);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,18 @@
+pub mod aabb;
+pub mod assets;
+pub mod font;
+pub mod framebuffer;
+pub mod ray_intersect;
+pub mod cube;
+pub mod sphere;
+pub mod color;
+pub mod camera;
+pub mod light;
+pub mod material;
+pub mod skybox;
+pub mod texture;
+pub mod ray_casting;
+pub mod scene;
+pub mod settings;
+pub mod transform;
+pub mod triangle;
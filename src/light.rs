@@ -1,18 +1,260 @@
 use nalgebra_glm::Vec3;
+use crate::aabb::Aabb;
 use crate::color::Color;
+use crate::material::Material;
+use crate::ray_intersect::{RayIntersect, Intersect, PrimitiveKind};
+use crate::texture::Texture;
 
+// Máscara por defecto: la luz afecta a todos los objetos
+pub const ALL_LIGHTS: u32 = u32::MAX;
+
+// Cono de un foco: eje y semiángulos (radianes) donde empieza y termina la caída del borde
+#[derive(Debug, Clone)]
+pub struct Spot {
+    pub direction: Vec3,
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+    // Textura proyectada (gobo) que multiplica el color de la luz dentro del cono
+    pub gobo: Option<Texture>,
+}
+
+// Tipo de emisor; el bucle de sombreado elige dirección y sombras según él
+#[derive(Debug, Clone)]
+pub enum LightKind {
+    // Luz puntual; con `radius` > 0 es una esfera y proyecta sombras suaves
+    Point { radius: f32 },
+    // Luz muy lejana (sol): todos los rayos llegan paralelos a `direction`
+    // (hacia donde viaja la luz) y no hay caída con la distancia
+    Directional { direction: Vec3 },
+    // Foco: solo ilumina dentro del cono
+    Spot(Spot),
+    // Rectángulo emisor centrado en `position` con aristas completas `u_edge` y `v_edge`
+    Rect { u_edge: Vec3, v_edge: Vec3 },
+}
+
+#[derive(Debug, Clone)]
 pub struct Light {
     pub position: Vec3,
     pub color: Color,
     pub intensity: f32,
+    pub light_mask: u32,
+    // Atenuación cuadrática con la distancia: intensidad / (1 + k·d²); 0 = sin caída
+    pub attenuation: f32,
+    pub kind: LightKind,
 }
 
+// Distancia a la que se coloca el objetivo de los rayos de sombra de una luz direccional
+const DIRECTIONAL_DISTANCE: f32 = 1e6;
+
 impl Light {
     pub fn new(position: Vec3, color: Color, intensity: f32) -> Self {
+        Light::point(position, color, intensity, 0.0)
+    }
+
+    pub fn point(position: Vec3, color: Color, intensity: f32, radius: f32) -> Self {
         Light {
             position,
             color,
             intensity,
+            light_mask: ALL_LIGHTS,
+            attenuation: 0.0,
+            kind: LightKind::Point { radius: radius.max(0.0) },
+        }
+    }
+
+    pub fn directional(direction: Vec3, color: Color, intensity: f32) -> Self {
+        Light {
+            kind: LightKind::Directional { direction: direction.normalize() },
+            ..Light::new(Vec3::zeros(), color, intensity)
+        }
+    }
+
+    pub fn spot(position: Vec3, direction: Vec3, color: Color, intensity: f32, inner_angle: f32, outer_angle: f32) -> Self {
+        Light {
+            kind: LightKind::Spot(Spot {
+                direction: direction.normalize(),
+                inner_angle,
+                outer_angle: outer_angle.max(inner_angle),
+                gobo: None,
+            }),
+            ..Light::new(position, color, intensity)
+        }
+    }
+
+    pub fn rect(center: Vec3, u_edge: Vec3, v_edge: Vec3, color: Color, intensity: f32) -> Self {
+        Light {
+            kind: LightKind::Rect { u_edge, v_edge },
+            ..Light::new(center, color, intensity)
+        }
+    }
+
+    pub fn spot_cone(&self) -> Option<&Spot> {
+        match &self.kind {
+            LightKind::Spot(spot) => Some(spot),
+            _ => None,
+        }
+    }
+
+    // Dirección unitaria desde `point` hacia la luz y distancia hasta ella
+    // (infinita para las direccionales)
+    pub fn direction_from(&self, point: &Vec3) -> (Vec3, f32) {
+        match self.kind {
+            LightKind::Directional { direction } => (-direction, f32::INFINITY),
+            _ => {
+                let to_light = self.position - point;
+                (to_light.normalize(), to_light.magnitude())
+            }
+        }
+    }
+
+    // Punto hacia el que se lanzan los rayos de sombra desde `point`
+    pub fn shadow_target(&self, point: &Vec3) -> Vec3 {
+        match self.kind {
+            LightKind::Directional { direction } => point - direction * DIRECTIONAL_DISTANCE,
+            _ => self.position,
         }
     }
+
+    pub fn with_mask(mut self, mask: u32) -> Self {
+        self.light_mask = mask;
+        self
+    }
+
+    pub fn with_attenuation(mut self, k: f32) -> Self {
+        self.attenuation = k;
+        self
+    }
+
+    // Proyecta una textura desde el foco; sin cono no tiene efecto
+    pub fn with_gobo(mut self, gobo: Texture) -> Self {
+        if let LightKind::Spot(spot) = &mut self.kind {
+            spot.gobo = Some(gobo);
+        }
+        self
+    }
+
+    // Color de la luz que llega a `point`: con gobo, la textura se muestrea en
+    // las coordenadas del punto proyectado sobre la sección del cono exterior
+    pub fn color_at(&self, point: &Vec3) -> Color {
+        let LightKind::Spot(Spot { direction, outer_angle, gobo: Some(gobo), .. }) = &self.kind else {
+            return self.color;
+        };
+        let to_point = point - self.position;
+        let depth = to_point.dot(direction);
+        if depth <= 0.0 {
+            return self.color;
+        }
+
+        // base ortonormal alrededor del eje del foco
+        let helper = if direction.y.abs() < 0.99 { Vec3::y() } else { Vec3::x() };
+        let right = direction.cross(&helper).normalize();
+        let up = right.cross(direction);
+        let extent = depth * outer_angle.tan().max(1e-4);
+        let u = 0.5 + 0.5 * to_point.dot(&right) / extent;
+        let v = 0.5 - 0.5 * to_point.dot(&up) / extent;
+        self.color.modulate(&gobo.sample(u.clamp(0.0, 1.0), v.clamp(0.0, 1.0)))
+    }
+
+    // Intensidad efectiva a una distancia dada (las direccionales no decaen)
+    pub fn intensity_at(&self, distance: f32) -> f32 {
+        if !distance.is_finite() {
+            return self.intensity;
+        }
+        self.intensity / (1.0 + self.attenuation * distance * distance)
+    }
+
+    // Factor del cono del foco en un punto: 1 dentro del ángulo interior, 0 fuera
+    // del exterior y una transición suave entre ambos; 1 para luces sin cono
+    pub fn spot_factor(&self, point: &Vec3) -> f32 {
+        let LightKind::Spot(spot) = &self.kind else {
+            return 1.0;
+        };
+        let cos = spot.direction.dot(&(point - self.position).normalize());
+        let (cos_inner, cos_outer) = (spot.inner_angle.cos(), spot.outer_angle.cos());
+        if cos >= cos_inner {
+            return 1.0;
+        }
+        if cos <= cos_outer {
+            return 0.0;
+        }
+        let t = (cos - cos_outer) / (cos_inner - cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+
+    // Una luz ilumina un material solo si comparten al menos un bit de máscara
+    pub fn affects(&self, mask: u32) -> bool {
+        self.light_mask & mask != 0
+    }
+}
+
+// Luz rectangular tipo softbox: `u_edge` y `v_edge` son las aristas completas
+// del rectángulo. Como objeto de la escena se ve como un quad emisivo.
+pub struct RectLight {
+    pub center: Vec3,
+    pub u_edge: Vec3,
+    pub v_edge: Vec3,
+    pub color: Color,
+    pub intensity: f32,
+}
+
+impl RectLight {
+    pub fn new(center: Vec3, u_edge: Vec3, v_edge: Vec3, color: Color, intensity: f32) -> Self {
+        RectLight { center, u_edge, v_edge, color, intensity }
+    }
+
+    // Luz equivalente para el bucle de sombreado
+    pub fn light(&self) -> Light {
+        Light::rect(self.center, self.u_edge, self.v_edge, self.color, self.intensity)
+    }
+}
+
+impl RayIntersect for RectLight {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect<'_> {
+        let normal = self.u_edge.cross(&self.v_edge).normalize();
+        let denom = ray_direction.dot(&normal);
+        if denom.abs() < 1e-6 {
+            return Intersect::empty();
+        }
+
+        let t = (self.center - ray_origin).dot(&normal) / denom;
+        if t <= 0.0 {
+            return Intersect::empty();
+        }
+
+        let point = ray_origin + ray_direction * t;
+        let local = point - self.center;
+        let a = local.dot(&self.u_edge) / self.u_edge.dot(&self.u_edge);
+        let b = local.dot(&self.v_edge) / self.v_edge.dot(&self.v_edge);
+        if a.abs() > 0.5 || b.abs() > 0.5 {
+            return Intersect::empty();
+        }
+
+        // Emite por ambas caras: la normal siempre mira hacia el rayo
+        Intersect::new(point, normal, t, self.material())
+            .with_uv(a + 0.5, b + 0.5)
+            .with_tangent(self.u_edge.normalize())
+            .with_face(ray_direction)
+    }
+
+    fn is_light(&self) -> bool {
+        true
+    }
+
+    fn kind(&self) -> PrimitiveKind {
+        PrimitiveKind::Light
+    }
+
+    fn material(&self) -> Material {
+        Material::black().with_emission(self.color * self.intensity)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let (u, v) = (self.u_edge * 0.5, self.v_edge * 0.5);
+        Aabb::from_points(&[
+            self.center - u - v,
+            self.center + u - v,
+            self.center - u + v,
+            self.center + u + v,
+        ])
+    }
 }
@@ -1,7 +1,9 @@
 use crate::color::Color;
+use crate::light::ALL_LIGHTS;
 use crate::texture::Texture;
+use std::borrow::Cow;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Material {
     pub diffuse: Color,
     pub specular: f32,
@@ -13,6 +15,24 @@ pub struct Material {
     pub ior: f32,
     pub roughness: f32,
     pub emission: Option<Color>,
+    pub light_mask: u32,
+    // Estiramiento del brillo a lo largo de la tangente (-1..1, 0 = isótropo)
+    pub anisotropy: f32,
+    // Multiplicador del término ambiental (0 = sin luz ambiente)
+    pub ambient: f32,
+    // Multiplicador de UV (repeticiones) aplicado antes de muestrear la textura
+    pub uv_scale: f32,
+    // Tinte de la sombra que proyecta este objeto (filtro, 255 = deja pasar);
+    // None = sombra neutra
+    pub shadow_color: Option<Color>,
+    // Espesor de una película delgada en nanómetros (burbuja, mancha de aceite);
+    // tiñe el reflejo por interferencia. None = sin película
+    pub thin_film: Option<f32>,
+    // false: la superficie ignora las sombras (neones, objetos sin iluminar)
+    pub receives_shadows: bool,
+    // Filtro de la luz refractada (255 = deja pasar todo): absorción y tinte
+    // del medio transparente
+    pub transmission: Color,
 }
 
 impl Material {
@@ -28,6 +48,14 @@ impl Material {
             ior: 1.0,
             roughness: 0.0,
             emission: None,
+            light_mask: ALL_LIGHTS,
+            anisotropy: 0.0,
+            ambient: 1.0,
+            uv_scale: 1.0,
+            shadow_color: None,
+            thin_film: None,
+            receives_shadows: true,
+            transmission: Color::new(255.0, 255.0, 255.0),
         }
     }
 
@@ -43,19 +71,88 @@ impl Material {
             ior: 1.5,
             roughness: 0.0,
             emission: None,
+            light_mask: ALL_LIGHTS,
+            anisotropy: 0.0,
+            ambient: 1.0,
+            uv_scale: 1.0,
+            shadow_color: None,
+            thin_film: None,
+            receives_shadows: true,
+            transmission: Color::new(255.0, 255.0, 255.0),
         }
     }
 
+    // Vidrio/agua: reflectividad de Fresnel a incidencia normal a partir del IOR
+    // y el resto se transmite, sin término difuso. La fracción absorbida (0-1)
+    // oscurece la luz refractada; el color del medio se añade con `with_tint`
+    pub fn dielectric(ior: f32, absorption: f32) -> Self {
+        let r0 = ((ior - 1.0) / (ior + 1.0)).powi(2);
+        let pass = 255.0 * (1.0 - absorption.clamp(0.0, 1.0));
+        Material::new(Color::black(), 100.0, [0.2, 0.8])
+            .with_ior(ior)
+            .with_reflectivity(r0)
+            .with_transparency(1.0 - r0)
+            .with_tint(Color::new(pass, pass, pass))
+    }
+
+    // Metal: la rugosidad (0-1) baja la reflectividad y ensancha el brillo especular
+    pub fn metal(color: Color, roughness: f32) -> Self {
+        let r = roughness.clamp(0.0, 1.0);
+        Material::new(color, 120.0 - 100.0 * r, [0.2 + 0.4 * r, 0.8 - 0.4 * r])
+            .with_reflectivity(0.9 - 0.3 * r)
+            .with_roughness(r)
+    }
+
+    // Parámetros de artista: color base en sRGB 0-1, metalicidad y rugosidad 0-1.
+    // Metal 1 equivale a `Material::metal`; metal 0 es un dieléctrico opaco
+    // (plástico, pintura) con el 4 % de reflejo de Fresnel que se apaga con la
+    // rugosidad. Los valores intermedios interpolan entre ambos
+    pub fn from_physical(base_color: [f32; 3], metalness: f32, roughness: f32) -> Self {
+        let [r, g, b] = base_color.map(|c| c.clamp(0.0, 1.0) * 255.0);
+        let color = Color::from_srgb(r, g, b);
+        let m = metalness.clamp(0.0, 1.0);
+        let rough = roughness.clamp(0.0, 1.0);
+        let metal = Material::metal(color, rough);
+        let lerp = |dielectric: f32, metal: f32| dielectric + (metal - dielectric) * m;
+        Material::new(
+            color,
+            metal.specular,
+            [lerp(0.9, metal.albedo[0]), lerp(0.3 * (1.0 - rough), metal.albedo[1])],
+        )
+        .with_reflectivity(lerp(0.04 * (1.0 - rough), metal.reflectivity))
+        .with_roughness(rough)
+    }
+
+    // Espejo perfecto: solo reflejo, sin difuso, especular ni ambiente
+    pub fn mirror() -> Self {
+        Material::black().with_reflectivity(1.0).with_ambient(0.0)
+    }
+
     pub fn with_texture(mut self, texture: Texture) -> Self {
         self.texture = Some(texture);
         self
     }
 
+    // Misma textura con otra repetición, sin duplicar su definición
+    pub fn with_texture_scaled(mut self, texture: Texture, uv_scale: f32) -> Self {
+        self.texture = Some(texture);
+        self.uv_scale = uv_scale;
+        self
+    }
+
     pub fn with_reflectivity(mut self, r: f32) -> Self { self.reflectivity = r; self }
     pub fn with_transparency(mut self, t: f32) -> Self { self.transparency = t; self }
     pub fn with_ior(mut self, ior: f32) -> Self { self.ior = ior; self }
     pub fn with_roughness(mut self, r: f32) -> Self { self.roughness = r; self }
     pub fn with_emission(mut self, c: Color) -> Self { self.emission = Some(c); self }
+    pub fn with_light_mask(mut self, mask: u32) -> Self { self.light_mask = mask; self }
+    pub fn with_anisotropy(mut self, a: f32) -> Self { self.anisotropy = a; self }
+    pub fn with_ambient(mut self, a: f32) -> Self { self.ambient = a; self }
+    pub fn with_shadow_color(mut self, c: Color) -> Self { self.shadow_color = Some(c); self }
+    pub fn with_thin_film(mut self, thickness_nm: f32) -> Self { self.thin_film = Some(thickness_nm); self }
+    pub fn with_receives_shadows(mut self, receives: bool) -> Self { self.receives_shadows = receives; self }
+    // Se acumula sobre el filtro actual (p. ej. la absorción de `dielectric`)
+    pub fn with_tint(mut self, c: Color) -> Self { self.transmission = self.transmission.modulate(&c); self }
 
     pub fn black() -> Self {
         Self {
@@ -69,6 +166,69 @@ impl Material {
             ior: 1.0,
             roughness: 0.0,
             emission: None,
+            light_mask: ALL_LIGHTS,
+            anisotropy: 0.0,
+            ambient: 1.0,
+            uv_scale: 1.0,
+            shadow_color: None,
+            thin_film: None,
+            receives_shadows: true,
+            transmission: Color::new(255.0, 255.0, 255.0),
         }
     }
 }
+
+// Para `Intersect::new`: un material prestado del objeto o uno propio del impacto
+impl<'a> From<&'a Material> for Cow<'a, Material> {
+    fn from(material: &'a Material) -> Self {
+        Cow::Borrowed(material)
+    }
+}
+
+impl From<Material> for Cow<'_, Material> {
+    fn from(material: Material) -> Self {
+        Cow::Owned(material)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: [f32; 3] = [0.8, 0.5, 0.2];
+
+    // metalness 0: dieléctrico con el F0 ≈ 0.04 de la mayoría de materiales
+    #[test]
+    fn zero_metalness_is_dielectric() {
+        for rough in [0.0, 0.5, 1.0] {
+            let m = Material::from_physical(BASE, 0.0, rough);
+            assert!((m.albedo[0] - 0.9).abs() < 1e-6);
+            assert!((m.albedo[1] - 0.3 * (1.0 - rough)).abs() < 1e-6);
+            assert!((m.reflectivity - 0.04 * (1.0 - rough)).abs() < 1e-6);
+            assert_eq!(m.roughness, rough);
+        }
+    }
+
+    // metalness 1: lo mismo que `Material::metal` con el color decodificado
+    #[test]
+    fn full_metalness_matches_metal() {
+        for rough in [0.0, 0.5, 1.0] {
+            let m = Material::from_physical(BASE, 1.0, rough);
+            let metal = Material::metal(Color::from_srgb(BASE[0] * 255.0, BASE[1] * 255.0, BASE[2] * 255.0), rough);
+            assert!((m.albedo[0] - metal.albedo[0]).abs() < 1e-6);
+            assert!((m.albedo[1] - metal.albedo[1]).abs() < 1e-6);
+            assert!((m.reflectivity - metal.reflectivity).abs() < 1e-6);
+            assert_eq!(m.specular, metal.specular);
+            assert_eq!(m.diffuse, metal.diffuse);
+        }
+    }
+
+    #[test]
+    fn out_of_range_inputs_are_clamped() {
+        let m = Material::from_physical([2.0, -1.0, 0.5], 3.0, -1.0);
+        let expected = Material::from_physical([1.0, 0.0, 0.5], 1.0, 0.0);
+        assert_eq!(m.diffuse, expected.diffuse);
+        assert_eq!(m.reflectivity, expected.reflectivity);
+        assert_eq!(m.roughness, 0.0);
+    }
+}
@@ -1,43 +1,202 @@
 use nalgebra_glm::Vec3;
+use half::f16;
+use rand::Rng;
 use rayon::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use crate::color::Color;
 use crate::framebuffer::Framebuffer;
-use crate::light::Light;
+use crate::light::{Light, LightKind};
 use crate::ray_intersect::{Intersect, RayIntersect};
+use crate::scene::Scene;
+use crate::settings::{Background, ComponentMask, RenderMode, RenderSettings, SamplePattern, ToneMap};
 use crate::skybox::Skybox;
+use crate::texture::SampleContext;
 
 const SHADOW_BIAS: f32 = 1e-4;
-const MAX_RAY_DEPTH: u32 = 3;
+
+// Rebotes acumulados por un rayo, contados por separado para reflexión y
+// refracción (ver `max_reflection_depth` / `max_refraction_depth`)
+#[derive(Debug, Clone, Copy, Default)]
+struct RayDepth {
+    reflection: u32,
+    refraction: u32,
+}
+
+impl RayDepth {
+    // Rayo de cámara (aún no ha rebotado)
+    fn is_primary(&self) -> bool {
+        self.reflection == 0 && self.refraction == 0
+    }
+
+    fn reflected(self) -> Self {
+        RayDepth { reflection: self.reflection + 1, ..self }
+    }
+
+    fn refracted(self) -> Self {
+        RayDepth { refraction: self.refraction + 1, ..self }
+    }
+}
+
+// Diferenciales de rayo por diferencias finitas: los rayos (origen, dirección)
+// del (sub)píxel vecino en x y en y acompañan al principal y rebotan con él,
+// así la huella del píxel se conoce también en reflejos y refracciones
+#[derive(Debug, Clone, Copy)]
+struct RayDifferential {
+    x: (Vec3, Vec3),
+    y: (Vec3, Vec3),
+}
+
+impl RayDifferential {
+    // Impactos de los rayos vecinos contra el objeto golpeado por el principal;
+    // None en las siluetas, donde alguno no lo toca
+    fn hits<'a>(&self, object: &'a dyn RayIntersect) -> Option<(Intersect<'a>, Intersect<'a>)> {
+        let hx = object.ray_intersect(&self.x.0, &self.x.1);
+        let hy = object.ray_intersect(&self.y.0, &self.y.1);
+        (hx.is_intersecting && hy.is_intersecting).then_some((hx, hy))
+    }
+
+    fn reflected((hx, hy): &(Intersect<'_>, Intersect<'_>), dx: &Vec3, dy: &Vec3) -> Self {
+        let bounce = |hit: &Intersect, d: &Vec3| {
+            let dir = reflect(&d.normalize(), &hit.normal).normalize();
+            (offset_origin(hit, &dir), dir)
+        };
+        RayDifferential { x: bounce(hx, dx), y: bounce(hy, dy) }
+    }
+
+    // Si alguno de los vecinos sufre reflexión total se pierde la huella
+    fn refracted((hx, hy): &(Intersect<'_>, Intersect<'_>), dx: &Vec3, dy: &Vec3) -> Option<Self> {
+        let bend = |hit: &Intersect, d: &Vec3| {
+            let eta = hit.material.ior.max(1.0);
+            let eta_ratio = if hit.front_face { 1.0 / eta } else { eta };
+            let dir = refract(&d.normalize(), &hit.normal, eta_ratio)?.normalize();
+            Some((offset_origin(hit, &dir), dir))
+        };
+        Some(RayDifferential { x: bend(hx, dx)?, y: bend(hy, dy)? })
+    }
+}
+
+// Variación de las UV entre el impacto principal y los de sus vecinos
+fn uv_derivatives(hit: &Intersect, (hx, hy): &(Intersect<'_>, Intersect<'_>)) -> Option<[(f32, f32); 2]> {
+    let (u, v) = hit.uv?;
+    let (ux, vx) = hx.uv?;
+    let (uy, vy) = hy.uv?;
+    Some([(ux - u, vx - v), (uy - u, vy - v)])
+}
+
+// Origen de un rayo secundario: desplazado hacia el lado de la superficie por
+// el que sale `dir`, para no volver a chocar con ella
+fn offset_origin(hit: &Intersect, dir: &Vec3) -> Vec3 {
+    let bias = hit.normal * SHADOW_BIAS;
+    if dir.dot(&hit.normal) < 0.0 { hit.point - bias } else { hit.point + bias }
+}
 
 #[inline(always)]
 fn reflect(incident: &Vec3, normal: &Vec3) -> Vec3 {
     incident - 2.0 * incident.dot(normal) * normal
 }
 
+// `normal` mira hacia el rayo; `eta_ratio` es n_origen / n_destino
 #[inline(always)]
-fn refract(incident: &Vec3, normal: &Vec3, eta: f32) -> Option<Vec3> {
-    let cosi = (-incident.dot(normal)).clamp(-1.0, 1.0);
-    let mut n = *normal;
-    let mut etai = 1.0;
-    let mut etat = eta;
-    let mut cosi_local = cosi;
-    if cosi < 0.0 {
-        cosi_local = -cosi;
-        n = -n;
-        core::mem::swap(&mut etai, &mut etat);
-    }
-    let eta_ratio = etai / etat;
-    let k = 1.0 - eta_ratio * eta_ratio * (1.0 - cosi_local * cosi_local);
+fn refract(incident: &Vec3, normal: &Vec3, eta_ratio: f32) -> Option<Vec3> {
+    let cosi = (-incident.dot(normal)).clamp(0.0, 1.0);
+    let k = 1.0 - eta_ratio * eta_ratio * (1.0 - cosi * cosi);
     if k < 0.0 { None } else {
-        Some(eta_ratio * *incident + (eta_ratio * cosi_local - k.sqrt()) * n)
+        Some(eta_ratio * *incident + (eta_ratio * cosi - k.sqrt()) * normal)
     }
 }
 
+// true si algún objeto bloquea el segmento `a` -> `b`; la geometría de las
+// luces y el entorno no cuentan como oclusores
+pub fn occluded(a: &Vec3, b: &Vec3, objects: &[Box<dyn RayIntersect>]) -> bool {
+    blockers(a, b, objects, f32::INFINITY).next().is_some()
+}
+
+// Impacto más cercano de un rayo sin sombrear (selección, medidas, colisiones).
+// La distancia está en unidades de mundo y `object` indica el índice en `objects`
+pub fn trace_closest<'a>(origin: &Vec3, direction: &Vec3, objects: &'a [Box<dyn RayIntersect>]) -> Option<Intersect<'a>> {
+    let dir = direction.try_normalize(f32::EPSILON)?;
+    let hit = closest_hit(origin, &dir, objects, 0..objects.len(), &RenderSettings::default());
+    hit.is_intersecting.then_some(hit)
+}
+
+// Consulta de visibilidad común: impactos que bloquean el segmento `a` -> `b`.
+// `max_distance` limita hasta dónde cuentan los oclusores. El objeto del que
+// sale el segmento también cuenta (un objeto no convexo se puede sombrear a sí
+// mismo): el origen ya viene desplazado de su superficie y además se descartan
+// los impactos a menos de `SHADOW_BIAS`
+fn blockers<'a>(
+    a: &'a Vec3,
+    b: &Vec3,
+    objects: &'a [Box<dyn RayIntersect>],
+    max_distance: f32,
+) -> impl Iterator<Item = Intersect<'a>> + 'a {
+    let dir = (b - a).normalize();
+    let distance = (b - a).magnitude().min(max_distance);
+
+    objects
+        .iter()
+        .filter(|object| object.casts_shadows())
+        .map(move |object| object.ray_intersect(a, &dir))
+        .filter(move |hit| hit.is_intersecting && hit.distance > SHADOW_BIAS && hit.distance < distance - SHADOW_BIAS)
+}
+
+// Filtro de la luz que llega a `target` (escala de `Color`, 255 = sin sombra).
+// Un oclusor normal deja la sombra parcial neutra; los que tienen
+// `shadow_color` la tiñen y se acumulan si hay varios
 #[inline(always)]
-fn cast_shadow(intersect: &Intersect, light: &Light, objects: &[Box<dyn RayIntersect>]) -> f32 {
-    let light_dir = (light.position - intersect.point).normalize();
-    let light_distance = (light.position - intersect.point).magnitude();
+fn shadow_ray(
+    shadow_origin: &Vec3,
+    target: &Vec3,
+    objects: &[Box<dyn RayIntersect>],
+    settings: &RenderSettings,
+) -> Color {
+    // los oclusores más allá de `far` no cuentan
+    let mut filter = Color::new(255.0, 255.0, 255.0);
+    for hit in blockers(shadow_origin, target, objects, settings.far) {
+        match hit.material.shadow_color {
+            Some(tint) => filter = filter.modulate(&tint),
+            None => return Color::new(255.0, 255.0, 255.0) * 0.3, // sombra parcial
+        }
+    }
+    filter
+}
+
+// Punto uniforme dentro de la esfera unidad (por rechazo)
+fn random_in_unit_sphere(rng: &mut impl Rng) -> Vec3 {
+    loop {
+        let p = Vec3::new(
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        );
+        if p.magnitude_squared() <= 1.0 {
+            return p;
+        }
+    }
+}
+
+// Perturba una dirección dentro de un cono que crece con la rugosidad. Cada
+// rebote toma su propia muestra del generador del hilo, así que rebotes
+// sucesivos y píxeles vecinos no repiten el mismo jitter (evita bandas)
+fn glossy_direction(dir: &Vec3, normal: &Vec3, roughness: f32) -> Vec3 {
+    if roughness <= 0.0 {
+        return *dir;
+    }
+    let jitter = random_in_unit_sphere(&mut rand::thread_rng());
+    let perturbed = (dir + jitter * (roughness * roughness * 0.5)).normalize();
+    // si la perturbación cruza la superficie se usa la dirección ideal
+    if perturbed.dot(normal).signum() == dir.dot(normal).signum() { perturbed } else { *dir }
+}
+
+fn cast_shadow(
+    intersect: &Intersect,
+    light: &Light,
+    objects: &[Box<dyn RayIntersect>],
+    settings: &RenderSettings,
+) -> Color {
+    let (light_dir, _) = light.direction_from(&intersect.point);
+    let target = light.shadow_target(&intersect.point);
 
     let offset_normal = intersect.normal * SHADOW_BIAS;
     let shadow_origin = if light_dir.dot(&intersect.normal) < 0.0 {
@@ -46,13 +205,231 @@ fn cast_shadow(intersect: &Intersect, light: &Light, objects: &[Box<dyn RayInter
         intersect.point + offset_normal
     };
 
-    for object in objects {
-        let shadow_i = object.ray_intersect(&shadow_origin, &light_dir);
-        if shadow_i.is_intersecting && shadow_i.distance < light_distance {
-            return 0.3; // sombra parcial
+    // las luces con extensión propia muestrean su superficie
+    match light.kind {
+        LightKind::Rect { u_edge, v_edge } => {
+            return area_shadow(&shadow_origin, light.position, u_edge, v_edge, objects, settings);
+        }
+        LightKind::Point { radius } if radius > 0.0 => {
+            return sphere_shadow(&shadow_origin, light.position, radius, objects, settings);
+        }
+        _ => {}
+    }
+
+    if settings.shadow_cone > 0.0 {
+        return cone_shadow(&shadow_origin, &target, objects, intersect.object, settings);
+    }
+
+    if settings.shadow_taps <= 1 || settings.shadow_jitter <= 0.0 {
+        return shadow_ray(&shadow_origin, &target, objects, settings);
+    }
+
+    // PCF: promediar varios rayos hacia puntos cercanos a la luz
+    let mut rng = rand::thread_rng();
+    let mut sum = Color::black();
+    for _ in 0..settings.shadow_taps {
+        let jitter = random_in_unit_sphere(&mut rng) * settings.shadow_jitter;
+        sum = sum + shadow_ray(&shadow_origin, &(target + jitter), objects, settings);
+    }
+    sum * (1.0 / settings.shadow_taps as f32)
+}
+
+// Penumbra barata con un único rayo, al estilo de las sombras suaves por
+// sphere tracing: si el rayo no se bloquea, se busca el objeto que pasa más
+// cerca de él en proporción a la distancia recorrida (hueco / t) y la luz se
+// atenúa cuando ese hueco cabe dentro del cono. Es una aproximación: usa las
+// cajas envolventes como distancia al oclusor y solo oscurece el exterior de
+// la sombra dura, no reproduce una luz de área real
+fn cone_shadow(
+    shadow_origin: &Vec3,
+    target: &Vec3,
+    objects: &[Box<dyn RayIntersect>],
+    origin_object: Option<usize>,
+    settings: &RenderSettings,
+) -> Color {
+    let open = Color::new(255.0, 255.0, 255.0);
+    let hard = shadow_ray(shadow_origin, target, objects, settings);
+    if hard != open {
+        return hard;
+    }
+
+    let to_target = target - shadow_origin;
+    let length = to_target.magnitude().min(settings.far);
+    if length <= 0.0 {
+        return open;
+    }
+    let dir = to_target / length;
+
+    let mut visibility: f32 = 1.0;
+    for (idx, object) in objects.iter().enumerate() {
+        // el objeto de origen se salta aquí: su caja siempre toca el origen
+        if Some(idx) == origin_object || !object.casts_shadows() {
+            continue;
+        }
+        let bounds = object.bounding_box();
+        if !bounds.is_finite() {
+            continue;
+        }
+        // punto del rayo más próximo al centro de la caja
+        let t = (bounds.center() - shadow_origin).dot(&dir);
+        if t <= SHADOW_BIAS || t >= length {
+            continue;
+        }
+        let gap = bounds.distance_to(&(shadow_origin + dir * t));
+        visibility = visibility.min(gap / (t * settings.shadow_cone));
+    }
+
+    let umbra = open * 0.3;
+    umbra.blend(open, visibility.clamp(0.0, 1.0))
+}
+
+// Filtro medio de una luz rectangular, muestreada en una rejilla con jitter
+fn area_shadow(
+    shadow_origin: &Vec3,
+    center: Vec3,
+    u_edge: Vec3,
+    v_edge: Vec3,
+    objects: &[Box<dyn RayIntersect>],
+    settings: &RenderSettings,
+) -> Color {
+    let grid = (settings.area_light_samples.max(1) as f32).sqrt().ceil() as u32;
+    let mut rng = rand::thread_rng();
+    let mut sum = Color::black();
+    for i in 0..grid {
+        for j in 0..grid {
+            let s = (i as f32 + rng.gen_range(0.0..1.0)) / grid as f32 - 0.5;
+            let t = (j as f32 + rng.gen_range(0.0..1.0)) / grid as f32 - 0.5;
+            let target = center + u_edge * s + v_edge * t;
+            sum = sum + shadow_ray(shadow_origin, &target, objects, settings);
+        }
+    }
+    sum * (1.0 / (grid * grid) as f32)
+}
+
+// Filtro medio de una luz esférica de radio `radius`, con `area_light_samples`
+// puntos uniformes dentro de la esfera
+fn sphere_shadow(
+    shadow_origin: &Vec3,
+    center: Vec3,
+    radius: f32,
+    objects: &[Box<dyn RayIntersect>],
+    settings: &RenderSettings,
+) -> Color {
+    let samples = settings.area_light_samples.max(1);
+    let mut rng = rand::thread_rng();
+    let mut sum = Color::black();
+    for _ in 0..samples {
+        let offset = random_in_unit_sphere(&mut rng);
+        sum = sum + shadow_ray(shadow_origin, &(center + offset * radius), objects, settings);
+    }
+    sum * (1.0 / samples as f32)
+}
+
+// Brillo anisótropo (exponente de Ashikhmin-Shirley sobre el half vector):
+// anisotropy > 0 alarga el brillo a lo largo de la tangente, < 0 a lo largo de la bitangente
+fn anisotropic_highlight(normal: &Vec3, tangent: &Vec3, light_dir: &Vec3, view_dir: &Vec3, shininess: f32, anisotropy: f32) -> f32 {
+    let a = anisotropy.clamp(-0.95, 0.95);
+    let t = (tangent - normal * normal.dot(tangent)).normalize();
+    let b = normal.cross(&t);
+    let h = (light_dir + view_dir).normalize();
+
+    let n_dot_h = normal.dot(&h).max(0.0);
+    let sin2 = (1.0 - n_dot_h * n_dot_h).max(1e-6);
+    let exp_t = shininess * (1.0 - a);
+    let exp_b = shininess * (1.0 + a);
+    let exponent = (exp_t * h.dot(&t).powi(2) + exp_b * h.dot(&b).powi(2)) / sin2;
+    n_dot_h.powf(exponent)
+}
+
+fn local_shading(
+    closest: &Intersect,
+    ray_direction: &Vec3,
+    objects: &[Box<dyn RayIntersect>],
+    lights: &[Light],
+    settings: &RenderSettings,
+) -> Color {
+    // textura base si existe
+    let mut base_diffuse = closest.material.diffuse;
+    if let (Some(tex), Some((u, v))) = (&closest.material.texture, closest.uv) {
+        let s = closest.material.uv_scale;
+        let footprint = closest.uv_derivatives.map(|d| d.map(|(du, dv)| (du * s, dv * s)));
+        base_diffuse = tex.sample_with((u * s).fract(), (v * s).fract(), &SampleContext { time: settings.time, footprint });
+    }
+
+    // ambiental: irradiancia del entorno en la dirección de la normal
+    let ambient = if settings.environment {
+        Skybox::sample_irradiance(&Skybox::unrotate(&closest.normal, settings.sky_rotation))
+    } else {
+        Color::black()
+    };
+    let mut local = base_diffuse.modulate(&ambient) * (0.1 * closest.material.ambient);
+    let mut specular_sum = Color::black();
+
+    for light in lights {
+        if !light.affects(closest.material.light_mask) {
+            continue;
+        }
+
+        let (light_dir, light_distance) = light.direction_from(&closest.point);
+        let intensity = light.intensity_at(light_distance) * light.spot_factor(&closest.point);
+        // luces que aquí apenas aportan (lejanas con atenuación, fuera del cono)
+        // se saltan antes del rayo de sombra, que es lo caro
+        let reach = intensity * light.color.r.max(light.color.g).max(light.color.b) / 255.0;
+        if settings.light_cutoff > 0.0 && reach < settings.light_cutoff {
+            continue;
         }
+        let shadow = if closest.material.receives_shadows {
+            cast_shadow(closest, light, objects, settings)
+        } else {
+            Color::new(255.0, 255.0, 255.0)
+        };
+
+        let diffuse_strength = closest.normal.dot(&light_dir).max(0.0);
+        let light_color = light.color_at(&closest.point).modulate(&shadow);
+        let diffuse = base_diffuse.modulate(&light_color) * diffuse_strength * intensity;
+
+        let view_dir = (-ray_direction).normalize();
+        let n = closest.material.specular;
+        let highlight = match closest.tangent {
+            Some(tangent) if closest.material.anisotropy != 0.0 => {
+                let norm = if settings.normalized_specular { (n + 8.0) / (8.0 * std::f32::consts::PI) } else { 1.0 };
+                anisotropic_highlight(
+                    &closest.normal,
+                    &tangent,
+                    &light_dir,
+                    &view_dir,
+                    n,
+                    closest.material.anisotropy,
+                ) * norm
+            }
+            _ => {
+                let norm = if settings.normalized_specular { (n + 2.0) / (2.0 * std::f32::consts::PI) } else { 1.0 };
+                let reflect_dir = reflect(&-light_dir, &closest.normal);
+                view_dir.dot(&reflect_dir).max(0.0).powf(n) * norm
+            }
+        };
+        let specular = light_color * closest.material.albedo[1] * highlight * intensity;
+
+        local = local + diffuse;
+        specular_sum = specular_sum + specular;
+    }
+
+    // el especular de todas las luces se comprime junto, antes de sumarlo
+    local + soft_clamp(specular_sum, settings.specular_clamp)
+}
+
+// Compresión suave por canal hacia `limit`: hasta la mitad no cambia y por
+// encima se acerca a `limit` sin llegar (pendiente continua, sin escalón).
+// Quita los píxeles calientes aislados sin apagar el resto del brillo; 0 = sin límite
+fn soft_clamp(color: Color, limit: f32) -> Color {
+    if limit <= 0.0 {
+        return color;
     }
-    1.0
+    let knee = 0.5 * limit;
+    let channel = |c: f32| {
+        if c <= knee { c } else { knee + (c - knee) / (1.0 + (c - knee) / (limit - knee)) }
+    };
+    Color::new(channel(color.r), channel(color.g), channel(color.b))
 }
 
 fn cast_ray(
@@ -60,138 +437,1309 @@ fn cast_ray(
     ray_direction: &Vec3,
     objects: &[Box<dyn RayIntersect>],
     lights: &[Light],
-    depth: u32,
+    settings: &RenderSettings,
+    depth: RayDepth,
+    differential: Option<RayDifferential>,
 ) -> Color {
-    if depth > MAX_RAY_DEPTH {
-        return Skybox::sample_color(ray_direction);
+    if depth.reflection > settings.max_reflection_depth || depth.refraction > settings.max_refraction_depth {
+        return miss_color(ray_direction, settings, depth);
     }
 
+    let closest = closest_hit(ray_origin, ray_direction, objects, 0..objects.len(), settings);
+    shade(ray_direction, &closest, objects, lights, settings, depth, differential)
+}
+
+// Impacto más cercano entre los objetos indicados por `candidates`
+fn closest_hit<'a>(
+    ray_origin: &Vec3,
+    ray_direction: &Vec3,
+    objects: &'a [Box<dyn RayIntersect>],
+    candidates: impl Iterator<Item = usize>,
+    settings: &RenderSettings,
+) -> Intersect<'a> {
+    closest_hit_where(ray_origin, ray_direction, objects, candidates, settings, |_, _| true)
+}
+
+// Como `closest_hit`, descartando los impactos para los que `keep` devuelve false
+fn closest_hit_where<'a>(
+    ray_origin: &Vec3,
+    ray_direction: &Vec3,
+    objects: &'a [Box<dyn RayIntersect>],
+    candidates: impl Iterator<Item = usize>,
+    settings: &RenderSettings,
+    keep: impl Fn(usize, &Intersect) -> bool,
+) -> Intersect<'a> {
     let mut closest = Intersect::empty();
     let mut z = f32::INFINITY;
 
-    for obj in objects {
-        let i = obj.ray_intersect(ray_origin, ray_direction);
-        if i.is_intersecting && i.distance < z {
+    for idx in candidates {
+        let i = objects[idx].ray_intersect(ray_origin, ray_direction);
+        let in_range = i.distance >= settings.near && i.distance <= settings.far;
+        if i.is_intersecting && in_range && i.distance < z && keep(idx, &i) {
             z = i.distance;
             closest = i;
+            closest.object = Some(idx);
         }
     }
 
-    if !closest.is_intersecting {
-        return Skybox::sample_color(ray_direction);
+    closest
+}
+
+// Longitudes de onda (nm) representativas de los canales R, G y B
+const RGB_WAVELENGTHS: [f32; 3] = [650.0, 532.0, 450.0];
+
+// Interferencia de dos haces en una película delgada de índice `ior`: la
+// diferencia de camino 2·n·d·cos(θt) desfasa cada canal de forma distinta.
+// Devuelve un filtro (255 = sin cambio) para teñir el reflejo
+fn thin_film_tint(thickness: f32, ior: f32, incident: &Vec3, normal: &Vec3) -> Color {
+    let n = ior.max(1.0);
+    let cos_i = incident.normalize().dot(normal).abs().min(1.0);
+    let sin_t2 = (1.0 - cos_i * cos_i) / (n * n);
+    let cos_t = (1.0 - sin_t2).max(0.0).sqrt();
+    let path = 2.0 * n * thickness.max(0.0) * cos_t;
+    // el reflejo en la cara externa añade medio ciclo de desfase
+    let channel = |wavelength: f32| {
+        let phase = 2.0 * std::f32::consts::PI * path / wavelength + std::f32::consts::PI;
+        255.0 * (0.5 + 0.5 * phase.cos())
+    };
+    Color::new(
+        channel(RGB_WAVELENGTHS[0]),
+        channel(RGB_WAVELENGTHS[1]),
+        channel(RGB_WAVELENGTHS[2]),
+    )
+}
+
+// Damero en coordenadas equirectangulares para distinguir fallos de materiales negros
+fn miss_checker(ray_direction: &Vec3) -> Color {
+    let dir = ray_direction.normalize();
+    let u = 0.5 + dir.x.atan2(dir.z) / (2.0 * std::f32::consts::PI);
+    let v = 0.5 + dir.y.clamp(-1.0, 1.0).asin() / std::f32::consts::PI;
+    let s = (u * 16.0).floor() as i32 + (v * 8.0).floor() as i32;
+    if s % 2 == 0 { Color::new(255.0, 0.0, 255.0) } else { Color::black() }
+}
+
+// Los rayos de cámara (sin rebotes) y los secundarios pueden usar fondos distintos
+fn miss_color(ray_direction: &Vec3, settings: &RenderSettings, depth: RayDepth) -> Color {
+    if settings.mode == RenderMode::MissChecker {
+        return miss_checker(ray_direction);
+    }
+    if !settings.environment {
+        return Color::black();
     }
+    let background = if depth.is_primary() { settings.background } else { settings.reflection_background };
+    match background {
+        Background::Skybox => Skybox::sample_color(&Skybox::unrotate(ray_direction, settings.sky_rotation)),
+        Background::Solid(color) => color,
+    }
+}
 
-    // textura base si existe
-    let mut base_diffuse = closest.material.diffuse;
-    if let (Some(tex), Some((u, v))) = (closest.material.texture, closest.uv) {
-        base_diffuse = tex.sample(u.fract(), v.fract());
+// Color de los modos de depuración para el impacto primario; los fallos son negros
+fn debug_color(hit: &Intersect, mode: RenderMode) -> Color {
+    if !hit.is_intersecting {
+        return Color::black();
     }
+    match mode {
+        // normal exterior llevada de [-1, 1] a [0, 255]
+        RenderMode::Normals => {
+            let n = hit.outward_normal();
+            Color::new((n.x + 1.0) * 127.5, (n.y + 1.0) * 127.5, (n.z + 1.0) * 127.5)
+        }
+        // más claro cuanto más cerca; sin escala fija para que sirva sin `far`
+        RenderMode::Depth => Color::new(255.0, 255.0, 255.0) * (1.0 / (1.0 + hit.distance * 0.1)),
+        RenderMode::Uv => match hit.uv {
+            Some((u, v)) => Color::new(u.rem_euclid(1.0) * 255.0, v.rem_euclid(1.0) * 255.0, 0.0),
+            None => Color::new(255.0, 0.0, 255.0),
+        },
+        // color estable por índice (hash multiplicativo)
+        RenderMode::ObjectId => {
+            let h = (hit.object.map_or(0, |idx| idx as u32 + 1)).wrapping_mul(0x9E37_79B1);
+            Color::new(((h >> 24) & 0xFF) as f32, ((h >> 16) & 0xFF) as f32, ((h >> 8) & 0xFF) as f32)
+        }
+        RenderMode::Shaded | RenderMode::MissChecker => Color::black(),
+    }
+}
 
-    // iluminación local
-    let mut local = base_diffuse * 0.1; // ambiental
+// Depuración de focos: dentro del cono se mezcla el color de la luz y en la
+// zona de caída del borde un rojo intenso, para ver hacia dónde apunta
+fn spot_debug_tint(color: Color, point: &Vec3, lights: &[Light]) -> Color {
+    let mut out = color;
+    for light in lights.iter().filter(|l| l.spot_cone().is_some()) {
+        let f = light.spot_factor(point);
+        if f >= 1.0 {
+            out = out.blend(light.color, 0.25);
+        } else if f > 0.0 {
+            out = out.blend(Color::new(255.0, 40.0, 40.0), 0.6);
+        }
+    }
+    out
+}
 
-    for light in lights {
-        let light_dir = (light.position - closest.point).normalize();
-        let intensity = cast_shadow(&closest, light, objects);
+// Modelo de energía: cada impacto reparte la luz en tres fracciones que suman
+// como mucho 1: reflejo `r`, transmisión `t` y el resto (1 - r - t) para la
+// iluminación local. Si el material pide r + t > 1 se normalizan. En los
+// transparentes, r + t se reparte con Fresnel (Schlick), con la reflectancia
+// a incidencia normal que da el IOR, ((n - 1) / (n + 1))²: en ángulos
+// rasantes crece el reflejo. Con reflexión total interna todo lo transmitido
+// pasa al reflejo.
+// La emisión se suma aparte y el término local (Phong) no se normaliza
+fn energy_split(hit: &Intersect, ray_direction: &Vec3) -> (f32, f32) {
+    let mut r = hit.material.reflectivity.clamp(0.0, 1.0);
+    let mut t = hit.material.transparency.clamp(0.0, 1.0);
+    let budget = r + t;
+    if budget > 1.0 {
+        r /= budget;
+        t /= budget;
+    }
+    // sin salto de índice (ior 1) no hay interfaz que refleje más en rasante
+    if t <= 0.0 || hit.material.ior <= 1.0 {
+        return (r, t);
+    }
 
-        let diffuse_strength = closest.normal.dot(&light_dir).max(0.0);
-        let diffuse = base_diffuse * diffuse_strength * intensity;
+    let budget = r + t;
+    let eta = hit.material.ior;
+    let f0 = ((eta - 1.0) / (eta + 1.0)).powi(2);
+    let eta_ratio = if hit.front_face { 1.0 / eta } else { eta };
+    let cos_i = (-ray_direction.normalize().dot(&hit.normal)).clamp(0.0, 1.0);
+    // al salir hacia el medio menos denso Schlick usa el ángulo de transmisión
+    let cos = if eta_ratio > 1.0 {
+        let sin_t2 = eta_ratio * eta_ratio * (1.0 - cos_i * cos_i);
+        if sin_t2 >= 1.0 {
+            return (budget, 0.0);
+        }
+        (1.0 - sin_t2).sqrt()
+    } else {
+        cos_i
+    };
+    let fresnel = f0 + (1.0 - f0) * (1.0 - cos).powi(5);
+    (budget * fresnel, budget * (1.0 - fresnel))
+}
 
-        let reflect_dir = reflect(&-light_dir, &closest.normal);
-        let view_dir = (-ray_direction).normalize();
-        let specular = light.color
-            * closest.material.albedo[1]
-            * view_dir.dot(&reflect_dir).max(0.0).powf(closest.material.specular)
-            * intensity;
+fn shade(
+    ray_direction: &Vec3,
+    closest: &Intersect,
+    objects: &[Box<dyn RayIntersect>],
+    lights: &[Light],
+    settings: &RenderSettings,
+    depth: RayDepth,
+    differential: Option<RayDifferential>,
+) -> Color {
+    // los pases solo filtran el impacto de cámara; los rebotes se sombrean completos
+    let mask = if depth.is_primary() { settings.components } else { ComponentMask::ALL };
 
-        local = local + diffuse + specular;
+    if !closest.is_intersecting {
+        if !mask.contains(ComponentMask::BACKGROUND) {
+            return Color::black();
+        }
+        return miss_color(ray_direction, settings, depth);
     }
 
+    // huella del píxel en UV a partir de los impactos de los rayos vecinos
+    let neighbours = match (differential, closest.object) {
+        (Some(diff), Some(idx)) => diff.hits(objects[idx].as_ref()),
+        _ => None,
+    };
+    let with_footprint;
+    let closest = match &neighbours {
+        Some(hits) => {
+            with_footprint = Intersect { uv_derivatives: uv_derivatives(closest, hits), ..closest.clone() };
+            &with_footprint
+        }
+        None => closest,
+    };
+
     // Reflexión / Refracción / Emisión
-    let r = closest.material.reflectivity.clamp(0.0, 1.0);
-    let t = closest.material.transparency.clamp(0.0, 1.0);
+    let (r, t) = energy_split(closest, ray_direction);
     let base_w = (1.0 - r - t).max(0.0);
 
+    // iluminación local (o el sombreado personalizado si existe); los espejos
+    // puros no tienen término local y se saltan el bucle de luces
+    let local = if base_w <= 0.0 || !mask.contains(ComponentMask::DIRECT) {
+        Color::black()
+    } else {
+        match settings.shade_override {
+            Some(shade) => shade(closest, lights),
+            None => local_shading(closest, ray_direction, objects, lights, settings),
+        }
+    };
+
     let mut refl_col = Color::black();
-    if r > 0.0 && depth < MAX_RAY_DEPTH {
+    if r > 0.0 && depth.reflection < settings.max_reflection_depth && mask.contains(ComponentMask::REFLECTION) {
         let dir = reflect(&ray_direction.normalize(), &closest.normal).normalize();
-        let bias = closest.normal * SHADOW_BIAS;
-        let origin = if dir.dot(&closest.normal) < 0.0 {
-            closest.point - bias
-        } else {
-            closest.point + bias
-        };
-        refl_col = cast_ray(&origin, &dir, objects, lights, depth + 1);
+        let dir = glossy_direction(&dir, &closest.normal, closest.material.roughness);
+        let origin = offset_origin(closest, &dir);
+        let diff = differential.zip(neighbours.as_ref()).map(|(d, hits)| RayDifferential::reflected(hits, &d.x.1, &d.y.1));
+        refl_col = cast_ray(&origin, &dir, objects, lights, settings, depth.reflected(), diff);
+        if let Some(thickness) = closest.material.thin_film {
+            let tint = thin_film_tint(thickness, closest.material.ior, ray_direction, &closest.normal);
+            refl_col = refl_col.modulate(&tint);
+        }
     }
 
     let mut refr_col = Color::black();
-    if t > 0.0 && depth < MAX_RAY_DEPTH {
+    if t > 0.0 && depth.refraction < settings.max_refraction_depth && mask.contains(ComponentMask::REFRACTION) {
+        // al entrar se pasa del aire al material; al salir, al revés
         let eta = closest.material.ior.max(1.0);
-        if let Some(dir) = refract(&ray_direction.normalize(), &closest.normal, eta) {
-            let bias = closest.normal * SHADOW_BIAS;
-            let origin = if dir.dot(&closest.normal) < 0.0 {
-                closest.point - bias
-            } else {
-                closest.point + bias
-            };
-            refr_col = cast_ray(&origin, &dir.normalize(), objects, lights, depth + 1);
+        let eta_ratio = if closest.front_face { 1.0 / eta } else { eta };
+        if let Some(dir) = refract(&ray_direction.normalize(), &closest.normal, eta_ratio) {
+            let dir = glossy_direction(&dir.normalize(), &closest.normal, closest.material.roughness);
+            let origin = offset_origin(closest, &dir);
+            let diff = differential.zip(neighbours.as_ref()).and_then(|(d, hits)| RayDifferential::refracted(hits, &d.x.1, &d.y.1));
+            refr_col = cast_ray(&origin, &dir, objects, lights, settings, depth.refracted(), diff)
+                .modulate(&closest.material.transmission);
         }
     }
 
     let mut out_color = local * base_w + refl_col * r + refr_col * t;
-    if let Some(em) = closest.material.emission {
+    if let Some(em) = closest.material.emission && mask.contains(ComponentMask::EMISSION) {
         out_color = out_color + em;
     }
 
+    if settings.debug_spot_cones && depth.is_primary() {
+        out_color = spot_debug_tint(out_color, &closest.point, lights);
+    }
+
+    // niebla: cuanto más lejos el impacto, más se acerca al color de la niebla.
+    // El color propio de la niebla cuenta como parte del pase directo
+    if settings.fog_density > 0.0 {
+        let visibility = (-settings.fog_density * closest.distance).exp();
+        let fog = if mask.contains(ComponentMask::DIRECT) { settings.fog_color } else { Color::black() };
+        out_color = fog.blend(out_color, visibility);
+    }
+
     out_color
 }
 
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut f = 1.0;
+    let mut r = 0.0;
+    while index > 0 {
+        f /= base as f32;
+        r += f * (index % base) as f32;
+        index /= base;
+    }
+    r
+}
+
+// Desplazamientos de submuestra en [0, 1)² para los patrones deterministas
+fn sample_offsets(pattern: SamplePattern, samples: u32) -> Vec<(f32, f32)> {
+    if samples <= 1 {
+        return vec![(0.5, 0.5)];
+    }
+    match pattern {
+        SamplePattern::Random => Vec::new(),
+        SamplePattern::Grid | SamplePattern::RotatedGrid => {
+            // la rejilla más grande que cabe; si el número no es cuadrado las
+            // submuestras que sobran se completan con Halton
+            let g = (samples as f32).sqrt().floor().max(1.0) as u32;
+            // rejilla rotada atan(1/2): con 2x2 da el patrón RGSS clásico
+            let angle = if pattern == SamplePattern::RotatedGrid { 0.5f32.atan() } else { 0.0 };
+            let (sin, cos) = angle.sin_cos();
+            let mut offsets = Vec::with_capacity(samples as usize);
+            for j in 0..g {
+                for i in 0..g {
+                    let sx = (i as f32 + 0.5) / g as f32 - 0.5;
+                    let sy = (j as f32 + 0.5) / g as f32 - 0.5;
+                    let rx = sx * cos - sy * sin;
+                    let ry = sx * sin + sy * cos;
+                    offsets.push(((rx + 0.5).rem_euclid(1.0), (ry + 0.5).rem_euclid(1.0)));
+                }
+            }
+            offsets.extend((1..=samples - g * g).map(|i| (halton(i, 2), halton(i, 3))));
+            offsets
+        }
+        SamplePattern::Halton => (1..=samples).map(|i| (halton(i, 2), halton(i, 3))).collect(),
+    }
+}
+
+// Región de recorte validada: se ajusta al framebuffer y `None` si queda vacía
+fn crop_region(settings: &RenderSettings, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+    let (x0, y0, x1, y1) = match settings.crop {
+        Some((x0, y0, x1, y1)) => (x0 as u32, y0 as u32, x1 as u32, y1 as u32),
+        None => (0, 0, width, height),
+    };
+    let (x1, y1) = (x1.min(width), y1.min(height));
+    if x0 >= x1 || y0 >= y1 {
+        return None;
+    }
+    Some((x0, y0, x1, y1))
+}
+
+fn is_finite(col: Color) -> bool {
+    col.r.is_finite() && col.g.is_finite() && col.b.is_finite()
+}
+
+fn tone_map_channel(c: f32, op: ToneMap) -> f32 {
+    let x = c / 255.0;
+    let mapped = match op {
+        ToneMap::Clamp => x,
+        ToneMap::Reinhard => x / (1.0 + x),
+        ToneMap::Aces => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+    };
+    mapped * 255.0
+}
+
+// Color lineal del píxel -> 0xRRGGBB: exposición, curva de tono y sRGB opcional
+pub fn encode_pixel(col: Color, settings: &RenderSettings) -> u32 {
+    if settings.debug_nan && !is_finite(col) {
+        return 0xFF00FF;
+    }
+
+    let col = if settings.exposure != 0.0 { col * settings.exposure.exp2() } else { col };
+    let col = match settings.tone_map {
+        ToneMap::Clamp => col,
+        op => Color::new(
+            tone_map_channel(col.r.max(0.0), op),
+            tone_map_channel(col.g.max(0.0), op),
+            tone_map_channel(col.b.max(0.0), op),
+        ),
+    };
+    if settings.srgb_output { col.to_srgb().to_hex() } else { col.to_hex() }
+}
+
+// Vuelve a codificar el buffer `u32` a partir del flotante con los ajustes
+// dados (p. ej. otra exposición) sin trazar rayos; sin buffer flotante no hace nada
+pub fn resolve(framebuffer: &mut Framebuffer, settings: &RenderSettings) {
+    if let Some(hdr) = framebuffer.hdr.as_ref() {
+        framebuffer
+            .buffer
+            .par_iter_mut()
+            .zip(hdr.par_iter())
+            .for_each(|(pixel, &col)| *pixel = encode_pixel(col, settings));
+    } else if let Some(half) = framebuffer.hdr_half.as_ref() {
+        framebuffer
+            .buffer
+            .par_iter_mut()
+            .zip(half.par_iter())
+            .for_each(|(pixel, &col)| *pixel = encode_pixel(Color::from_f16(col), settings));
+    }
+}
+
+// Horquillado de exposición: guarda una imagen por cada valor de `stops`
+// (pasos, sustituyen a `settings.exposure`) a partir del buffer flotante, sin
+// volver a renderizar. Siempre se escriben PNG: de `path` ("bracket.png") solo
+// se usa la raíz, y cada archivo recibe el sufijo de su exposición
+// ("bracket_ev+1.0.png"). Falla si no hay buffer flotante o si alguna imagen
+// no se pudo escribir
+pub fn save_exposure_bracket(framebuffer: &Framebuffer, settings: &RenderSettings, stops: &[f32], path: &str) -> bool {
+    let Some(hdr) = framebuffer.hdr_colors() else {
+        return false;
+    };
+    let stem = path.rsplit_once('.').map_or(path, |(stem, _)| stem);
+    let mut ok = true;
+    for &stop in stops {
+        let bracket = RenderSettings { exposure: stop, ..*settings };
+        let bytes: Vec<u8> = hdr
+            .par_iter()
+            .flat_map_iter(|&col| Color::from_hex(encode_pixel(col, &bracket)).to_rgb8())
+            .collect();
+        let file = format!("{stem}_ev{stop:+.1}.png");
+        ok &= image::save_buffer_with_format(
+            &file,
+            &bytes,
+            framebuffer.width as u32,
+            framebuffer.height as u32,
+            image::ColorType::Rgb8,
+            image::ImageFormat::Png,
+        )
+        .is_ok();
+    }
+    ok
+}
+
+// Qué gira en cada frame de `save_turntable`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TurntableMode {
+    Camera,
+    // El entorno gira y la cámara queda quieta: se ve cómo responden los
+    // materiales a la luz en movimiento
+    Environment,
+    Both,
+}
+
+// Vuelta completa en `frames` imágenes del tamaño de `framebuffer`, guardadas
+// como "{stem}_000.{ext}", "{stem}_001.{ext}"... La cámara orbita alrededor de
+// su centro y/o el entorno gira a partir de `settings.sky_rotation`, sin tocar
+// el estado global. Falla si alguna imagen no se pudo escribir
+pub fn save_turntable(
+    framebuffer: &mut Framebuffer,
+    scene: &Scene,
+    camera: &crate::camera::Camera,
+    settings: &RenderSettings,
+    frames: u32,
+    mode: TurntableMode,
+    path: &str,
+) -> bool {
+    let (stem, ext) = path.rsplit_once('.').unwrap_or((path, "jpg"));
+    let mut ok = true;
+    for i in 0..frames {
+        let angle = 2.0 * std::f32::consts::PI * i as f32 / frames as f32;
+        let mut frame_camera = *camera;
+        if mode != TurntableMode::Environment {
+            frame_camera.orbit(angle, 0.0);
+        }
+        let mut frame_settings = *settings;
+        if mode != TurntableMode::Camera {
+            frame_settings.sky_rotation += angle;
+        }
+        render(framebuffer, &scene.objects, &frame_camera, &scene.lights, &frame_settings);
+        let bytes: Vec<u8> = framebuffer.buffer.iter().flat_map(|&px| Color::from_hex(px).to_rgb8()).collect();
+        let file = format!("{stem}_{i:03}.{ext}");
+        ok &= image::save_buffer(&file, &bytes, framebuffer.width as u32, framebuffer.height as u32, image::ColorType::Rgb8).is_ok();
+    }
+    ok
+}
+
 pub fn render(
     framebuffer: &mut Framebuffer,
     objects: &[Box<dyn RayIntersect>],
     camera: &crate::camera::Camera,
     lights: &[Light],
+    settings: &RenderSettings,
 ) {
-    let width = framebuffer.width as u32;
-    let height = framebuffer.height as u32;
-    let fw = width as f32;
-    let fh = height as f32;
-    let aspect_ratio = fw / fh;
-    let fov = std::f32::consts::PI / 3.0;
-    let scale = (fov * 0.5).tan();
-
-    // base de cámara
-    let forward = (camera.center - camera.position).normalize();
-    let right = forward.cross(&camera.up).normalize();
-    let up = right.cross(&forward).normalize();
-
-    // precálculo de px por columna
-    let mut px_row: Vec<f32> = Vec::with_capacity(width as usize);
-    for x in 0..width {
-        let px = (2.0 * (x as f32 + 0.5) / fw - 1.0) * aspect_ratio * scale;
-        px_row.push(px);
+    let (width, height) = (framebuffer.width, framebuffer.height);
+    if framebuffer.hdr.is_none() && framebuffer.hdr_half.is_none() && framebuffer.ids.is_none() {
+        render_into(&mut framebuffer.buffer, width, height, objects, camera, lights, settings);
+        return;
+    }
+    if width == 0 {
+        return;
     }
 
-    // render paralelo con Rayon
-    let buf_len = (width * height) as usize;
-
+    // con buffer flotante se guardan también los colores lineales sin recortar,
+    // y con buffer de ids el objeto golpeado en cada píxel
+    let hdr_rows: Vec<Option<&mut [Color]>> = match framebuffer.hdr.as_mut() {
+        Some(hdr) => hdr.chunks_mut(width).map(Some).collect(),
+        None => (0..height).map(|_| None).collect(),
+    };
+    let half_rows: Vec<Option<&mut [[f16; 3]]>> = match framebuffer.hdr_half.as_mut() {
+        Some(half) => half.chunks_mut(width).map(Some).collect(),
+        None => (0..height).map(|_| None).collect(),
+    };
+    let id_rows: Vec<Option<&mut [u32]>> = match framebuffer.ids.as_mut() {
+        Some(ids) => ids.chunks_mut(width).map(Some).collect(),
+        None => (0..height).map(|_| None).collect(),
+    };
+    let frame = FrameSetup::new(width, height, objects, camera, lights, settings);
     framebuffer
         .buffer
-        .par_iter_mut()
+        .par_chunks_mut(width)
+        .zip(hdr_rows)
+        .zip(half_rows)
+        .zip(id_rows)
         .enumerate()
-        .for_each(|(idx, pixel)| {
-            let x = (idx as u32) % width;
-            let y = (idx as u32) / width;
+        .for_each(|(y, (((row, hdr_row), half_row), id_row))| match half_row {
+            // en f16 la fila se calcula en f32 y se empaqueta al final (partiendo
+            // de lo que ya había, por si el recorte deja píxeles sin tocar)
+            Some(half_row) => {
+                let mut colors: Vec<Color> = half_row.iter().map(|&c| Color::from_f16(c)).collect();
+                frame.render_row(row, Some(&mut colors), id_row, y as u32, objects);
+                for (dst, col) in half_row.iter_mut().zip(&colors) {
+                    *dst = col.to_f16();
+                }
+            }
+            None => frame.render_row(row, hdr_row, id_row, y as u32, objects),
+        });
+    frame.report_non_finite();
+}
 
-            // acceso sin bounds-check
-            let px = unsafe { *px_row.get_unchecked(x as usize) };
-            let py = (1.0 - 2.0 * (y as f32 + 0.5) / fh) * scale;
+// Render sin ventana sobre un buffer 0xRRGGBB de `width * height` píxeles
+pub fn render_into(
+    buffer: &mut [u32],
+    width: usize,
+    height: usize,
+    objects: &[Box<dyn RayIntersect>],
+    camera: &crate::camera::Camera,
+    lights: &[Light],
+    settings: &RenderSettings,
+) {
+    assert_eq!(buffer.len(), width * height, "el buffer no coincide con width * height");
+    if width == 0 {
+        return;
+    }
+    let frame = FrameSetup::new(width, height, objects, camera, lights, settings);
 
-            let dir_cam = Vec3::new(px, py, -1.0);
-            let world_dir = (dir_cam.x * right + dir_cam.y * up - dir_cam.z * forward).normalize();
+    // render paralelo con Rayon, una fila por tarea
+    buffer
+        .par_chunks_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| frame.render_row(row, None, None, y as u32, objects));
+    frame.report_non_finite();
+}
 
-            let col = cast_ray(&camera.position, &world_dir, objects, lights, 0);
+// Renderiza solo la fila `y` de una imagen de `width * height` sobre `buffer_row`
+// (de `width` píxeles), para volcar el resultado fila a fila o repartir el
+// trabajo con otro sistema de hilos
+pub fn render_scanline(
+    buffer_row: &mut [u32],
+    y: usize,
+    width: usize,
+    height: usize,
+    scene: &Scene,
+    camera: &crate::camera::Camera,
+    settings: &RenderSettings,
+) {
+    assert_eq!(buffer_row.len(), width, "la fila no coincide con width");
+    assert!(y < height, "la fila {y} está fuera de la imagen");
+    let frame = FrameSetup::new(width, height, &scene.objects, camera, &scene.lights, settings);
+    frame.render_row(buffer_row, None, None, y as u32, &scene.objects);
+    frame.report_non_finite();
+}
 
-            *pixel = col.to_hex();
+// Todo lo que se calcula una vez por imagen antes de lanzar rayos
+struct FrameSetup<'a> {
+    width: u32,
+    height: u32,
+    crop: Option<(u32, u32, u32, u32)>,
+    scale: f32,
+    position: Vec3,
+    forward: Vec3,
+    right: Vec3,
+    up: Vec3,
+    lights: Vec<Light>,
+    visible: Vec<usize>,
+    // objetos cuya caja contiene la cámara
+    containing: Vec<bool>,
+    samples: u32,
+    offsets: Vec<(f32, f32)>,
+    settings: &'a RenderSettings<'a>,
+    // píxeles con canales no finitos (solo con `debug_nan`)
+    non_finite: AtomicUsize,
+}
+
+impl<'a> FrameSetup<'a> {
+    fn new(
+        width: usize,
+        height: usize,
+        objects: &[Box<dyn RayIntersect>],
+        camera: &crate::camera::Camera,
+        lights: &[Light],
+        settings: &'a RenderSettings<'a>,
+    ) -> Self {
+        let width = width as u32;
+        let height = height as u32;
+        let fov = camera.fov;
+
+        // base de cámara
+        let forward = (camera.center - camera.position).normalize();
+        let right = forward.cross(&camera.up).normalize();
+        let up = right.cross(&forward).normalize();
+
+        // los objetos emisivos aportan luces adicionales al bucle de sombreado
+        let mut scene_lights: Vec<Light> = lights.to_vec();
+        for obj in objects {
+            scene_lights.extend(obj.emissive_lights());
+        }
+
+        // los rayos primarios ignoran objetos completamente detrás de la cámara;
+        // reflexiones y sombras siguen considerando toda la escena
+        let visible: Vec<usize> = objects
+            .iter()
+            .enumerate()
+            .filter(|(_, obj)| !obj.bounding_box().is_behind(&camera.position, &forward))
+            .map(|(i, _)| i)
+            .collect();
+
+        let containing: Vec<bool> = objects
+            .iter()
+            .map(|obj| obj.bounding_box().contains(&camera.position))
+            .collect();
+
+        // submuestras por píxel (el patrón aleatorio se genera en cada píxel)
+        let samples = settings.samples_per_pixel.max(1);
+        let offsets = sample_offsets(settings.sample_pattern, samples);
+
+        FrameSetup {
+            width,
+            height,
+            crop: crop_region(settings, width, height),
+            scale: (fov * 0.5).tan(),
+            position: camera.position,
+            forward,
+            right,
+            up,
+            lights: scene_lights,
+            visible,
+            containing,
+            samples,
+            offsets,
+            settings,
+            non_finite: AtomicUsize::new(0),
+        }
+    }
+
+    fn primary_hit<'o>(&self, dir: &Vec3, objects: &'o [Box<dyn RayIntersect>], settings: &RenderSettings) -> Intersect<'o> {
+        closest_hit_where(
+            &self.position,
+            dir,
+            objects,
+            self.visible.iter().copied(),
+            settings,
+            |idx, hit| !self.hits_from_inside(idx, hit),
+        )
+    }
+
+    // Antialiasing analítico de aristas planas: si el píxel (su huella a la
+    // distancia del impacto) cruza una arista de la cara golpeada, se traza un
+    // rayo justo al otro lado y se mezcla según la fracción cubierta por la cara.
+    // Las primitivas curvas no dan distancia a arista y dependen del supermuestreo
+    fn edge_coverage(&self, col: Color, hit: &Intersect, objects: &[Box<dyn RayIntersect>], settings: &RenderSettings) -> Color {
+        let Some(idx) = hit.object else {
+            return col;
+        };
+        let Some((edge_distance, outward)) = objects[idx].edge_distance(&hit.point) else {
+            return col;
+        };
+        let footprint = hit.distance * 2.0 * self.scale / self.height as f32;
+        if edge_distance >= footprint * 0.5 {
+            return col;
+        }
+
+        let coverage = 0.5 + edge_distance / footprint;
+        let beyond = hit.point + outward * (edge_distance + footprint * 0.5);
+        let dir = (beyond - self.position).normalize();
+        let other = self.primary_hit(&dir, objects, settings);
+        let other_col = shade(&dir, &other, objects, &self.lights, settings, RayDepth::default(), None);
+        other_col.blend(col, coverage)
+    }
+
+    fn report_non_finite(&self) {
+        let count = self.non_finite.load(Ordering::Relaxed);
+        if count > 0 {
+            eprintln!("render: {count} píxeles con valores NaN/infinitos (marcados en magenta)");
+        }
+    }
+
+    // Cámara dentro de un sólido opaco: su cara trasera se descarta para ver a
+    // través de él. En los transparentes se conserva y se ve la superficie interior
+    fn hits_from_inside(&self, idx: usize, hit: &Intersect) -> bool {
+        self.containing[idx] && hit.material.transparency <= 0.0 && !hit.front_face
+    }
+
+    // Renderiza una fila; si hay fila flotante también guarda ahí el color sin
+    // recortar, y si hay fila de ids el objeto golpeado
+    fn render_row(
+        &self,
+        row: &mut [u32],
+        mut hdr_row: Option<&mut [Color]>,
+        mut id_row: Option<&mut [u32]>,
+        y: u32,
+        objects: &[Box<dyn RayIntersect>],
+    ) {
+        let Some((crop_x0, crop_y0, crop_x1, crop_y1)) = self.crop else {
+            return;
+        };
+        if y < crop_y0 || y >= crop_y1 {
+            return;
+        }
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let xi = x as u32;
+            if xi >= crop_x0 && xi < crop_x1 {
+                let (col, id) = self.render_pixel(xi, y, objects);
+                if let Some(hdr) = hdr_row.as_deref_mut() {
+                    hdr[x] = col;
+                }
+                if let Some(ids) = id_row.as_deref_mut() {
+                    ids[x] = id;
+                }
+                *pixel = self.encode(col);
+            }
+        }
+    }
+
+    // Color del píxel e id del objeto que ve su primera submuestra (índice + 1, 0 = fondo)
+    fn render_pixel(&self, x: u32, y: u32, objects: &[Box<dyn RayIntersect>]) -> (Color, u32) {
+        let fw = self.width as f32;
+        let fh = self.height as f32;
+        let aspect_ratio = fw / fh;
+        let lights = self.lights.as_slice();
+
+        let random_offsets: Vec<(f32, f32)>;
+        let pixel_offsets = if self.offsets.is_empty() {
+            let mut rng = rand::thread_rng();
+            random_offsets = (0..self.samples)
+                .map(|_| (rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0)))
+                .collect();
+            &random_offsets
+        } else {
+            &self.offsets
+        };
+
+        // se promedia en flotante; el recorte a 0-255 ocurre solo al final, así
+        // los valores HDR de reflexión/refracción (>255) pesan lo que deben en
+        // los bordes antialiasados en vez de recortarse antes de promediar
+        // con `render_scale` > 1 el píxel se divide en n x n subpíxeles que se
+        // reducen con un filtro de caja sobre `Color` lineal, antes de codificar
+        let n = self.settings.render_scale.max(1);
+        let sub_positions = (0..n * n).flat_map(|k| {
+            let (si, sj) = ((k % n) as f32, (k / n) as f32);
+            pixel_offsets.iter().map(move |&(ox, oy)| ((si + ox) / n as f32, (sj + oy) / n as f32))
         });
+
+        let mut sum = Color::black();
+        let mut count = 0;
+        let mut id = None;
+        let mut rng = rand::thread_rng();
+        for (ox, oy) in sub_positions {
+            // tiempo con jitter por submuestra (sin obturador todas usan `time`)
+            let mut sample_settings = *self.settings;
+            if self.settings.shutter > 0.0 {
+                sample_settings.time += rng.gen_range(0.0..self.settings.shutter);
+            }
+            let settings = &sample_settings;
+
+            let px = (2.0 * (x as f32 + ox) / fw - 1.0) * aspect_ratio * self.scale;
+            let py = (1.0 - 2.0 * (y as f32 + oy) / fh) * self.scale;
+
+            let dir_cam = Vec3::new(px, py, -1.0);
+            let world_dir = (dir_cam.x * self.right + dir_cam.y * self.up - dir_cam.z * self.forward).normalize();
+
+            let hit = self.primary_hit(&world_dir, objects, settings);
+            id.get_or_insert(hit.object.map_or(0, |idx| idx as u32 + 1));
+            if !matches!(settings.mode, RenderMode::Shaded | RenderMode::MissChecker) {
+                sum = sum + debug_color(&hit, settings.mode);
+                count += 1;
+                continue;
+            }
+            let differential = settings.anisotropic_textures.then(|| self.differential(px, py));
+            let mut col = shade(&world_dir, &hit, objects, lights, settings, RayDepth::default(), differential);
+            if settings.analytic_edge_aa {
+                col = self.edge_coverage(col, &hit, objects, settings);
+            }
+            sum = sum + col;
+            count += 1;
+        }
+        (sum * (1.0 / count as f32), id.unwrap_or(0))
+    }
+
+    // Rayos de cámara del (sub)píxel vecino en x y en y
+    fn differential(&self, px: f32, py: f32) -> RayDifferential {
+        let n = self.settings.render_scale.max(1) as f32;
+        let step_x = 2.0 * (self.width as f32 / self.height as f32) * self.scale / (self.width as f32 * n);
+        let step_y = 2.0 * self.scale / (self.height as f32 * n);
+        let ray = |px: f32, py: f32| (self.position, (px * self.right + py * self.up + self.forward).normalize());
+        RayDifferential { x: ray(px + step_x, py), y: ray(px, py - step_y) }
+    }
+
+    fn encode(&self, col: Color) -> u32 {
+        if self.settings.debug_nan && !is_finite(col) {
+            self.non_finite.fetch_add(1, Ordering::Relaxed);
+        }
+        encode_pixel(col, self.settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::material::Material;
+    use crate::sphere::Sphere;
+
+    fn white_furnace() -> RenderSettings<'static> {
+        let white = Background::Solid(Color::new(255.0, 255.0, 255.0));
+        // profundidad de sobra: al cortar los rebotes se pierde la parte reflejada
+        RenderSettings {
+            background: white,
+            reflection_background: white,
+            environment: true,
+            max_reflection_depth: 16,
+            max_refraction_depth: 16,
+            ..RenderSettings::default()
+        }
+    }
+
+    // Horno blanco: un dieléctrico sin absorción dentro de un entorno blanco
+    // uniforme no debe ganar ni perder energía, mire desde donde se mire
+    #[test]
+    fn dielectric_conserves_energy_in_white_furnace() {
+        let objects: Vec<Box<dyn RayIntersect>> = vec![Box::new(Sphere::new(Vec3::zeros(), 1.0, Material::dielectric(1.5, 0.0)))];
+        let settings = white_furnace();
+        let origin = Vec3::new(0.0, 0.0, 5.0);
+        for offset in [0.0, 0.5, 0.9, 0.99] {
+            let dir = (Vec3::new(offset, 0.0, 0.0) - origin).normalize();
+            let color = cast_ray(&origin, &dir, &objects, &[], &settings, RayDepth::default(), None);
+            for channel in [color.r, color.g, color.b] {
+                assert!((channel - 255.0).abs() < 1.0, "offset {offset}: {color:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn fresnel_reflectance_at_normal_incidence_comes_from_ior() {
+        let material = Material::dielectric(1.5, 0.0);
+        let hit = Intersect::new(Vec3::new(0.0, 0.0, 1.0), Vec3::z(), 4.0, material).with_face(&-Vec3::z());
+        let (r, t) = energy_split(&hit, &-Vec3::z());
+        assert!((r - 0.04).abs() < 1e-4);
+        assert!((r + t - 1.0).abs() < 1e-4);
+    }
+
+    // Un espejo perfecto frente a un cielo de color sólido refleja ese color:
+    // cubre el camino de reflexión de `cast_ray` hasta el fondo
+    #[test]
+    fn mirror_sphere_reflects_solid_sky() {
+        let sky = Color::new(40.0, 120.0, 200.0);
+        let settings = RenderSettings {
+            background: Background::Solid(sky),
+            reflection_background: Background::Solid(sky),
+            ..RenderSettings::default()
+        };
+        let objects: Vec<Box<dyn RayIntersect>> = vec![Box::new(Sphere::new(Vec3::zeros(), 1.0, Material::mirror()))];
+        let camera = Camera::new(Vec3::new(0.0, 0.0, 5.0), Vec3::zeros(), Vec3::y());
+        // el píxel central mira al espejo, no al fondo directamente
+        assert!(trace_closest(&camera.position, &-Vec3::z(), &objects).is_some());
+        let (width, height) = (9, 9);
+        let mut buffer = vec![0; width * height];
+        render_into(&mut buffer, width, height, &objects, &camera, &[], &settings);
+        assert_eq!(buffer[(height / 2) * width + width / 2], encode_pixel(sky, &settings));
+    }
+
+    // El borde de una esfera de vidrio se promedia en HDR: con 64 muestras la
+    // silueta toma valores entre el fondo y el interior que da 1 muestra
+    #[test]
+    fn glass_silhouette_is_antialiased() {
+        let settings = RenderSettings {
+            background: Background::Solid(Color::new(255.0, 255.0, 255.0)),
+            reflection_background: Background::Solid(Color::black()),
+            ..RenderSettings::default()
+        };
+        let objects: Vec<Box<dyn RayIntersect>> = vec![Box::new(Sphere::new(Vec3::zeros(), 1.0, Material::dielectric(1.5, 0.0)))];
+        let camera = Camera::new(Vec3::new(0.0, 0.0, 5.0), Vec3::zeros(), Vec3::y());
+        let (width, height) = (24, 24);
+        let center_row = |spp: u32| {
+            let mut buffer = vec![0; width * height];
+            let settings = RenderSettings { samples_per_pixel: spp, ..settings };
+            render_into(&mut buffer, width, height, &objects, &camera, &[], &settings);
+            buffer[(height / 2) * width..(height / 2 + 1) * width].iter().map(|&px| Color::from_hex(px).g).collect::<Vec<_>>()
+        };
+
+        let single = center_row(1);
+        let (lo, hi) = single.iter().fold((f32::MAX, f32::MIN), |(lo, hi), &g| (lo.min(g), hi.max(g)));
+        assert!(hi - lo > 100.0, "sin contraste entre fondo y vidrio: {single:?}");
+        let multi = center_row(64);
+        assert!(
+            multi.iter().any(|&g| g > lo + 10.0 && g < hi - 10.0),
+            "ningún píxel intermedio en la silueta: {multi:?}"
+        );
+    }
+
+    // Niveles intermedios (ni fondo ni esfera) en la franja central de la
+    // silueta de una esfera emisiva blanca sobre negro, con 4 submuestras: ahí
+    // el borde es casi vertical
+    fn silhouette_levels(pattern: SamplePattern) -> Vec<u32> {
+        let white = Material::black().with_emission(Color::new(255.0, 255.0, 255.0));
+        let objects: Vec<Box<dyn RayIntersect>> = vec![Box::new(Sphere::new(Vec3::zeros(), 1.0, white))];
+        let settings = RenderSettings {
+            samples_per_pixel: 4,
+            sample_pattern: pattern,
+            srgb_output: false,
+            background: Background::Solid(Color::black()),
+            components: ComponentMask::EMISSION | ComponentMask::BACKGROUND,
+            ..RenderSettings::default()
+        };
+        let camera = Camera::new(Vec3::new(0.0, 0.0, 5.0), Vec3::zeros(), Vec3::y());
+        let (width, height) = (48, 48);
+        let mut buffer = vec![0; width * height];
+        render_into(&mut buffer, width, height, &objects, &camera, &[], &settings);
+        let band = &buffer[(height / 2 - 4) * width..(height / 2 + 4) * width];
+        let mut levels: Vec<u32> = band.iter().map(|&px| px & 0xFF).filter(|&g| g > 0 && g < 255).collect();
+        levels.sort_unstable();
+        levels.dedup();
+        levels
+    }
+
+    #[test]
+    fn deterministic_patterns_antialias_silhouette() {
+        for pattern in [SamplePattern::Grid, SamplePattern::RotatedGrid, SamplePattern::Halton] {
+            assert!(!silhouette_levels(pattern).is_empty(), "{pattern:?} no da cobertura parcial");
+        }
+    }
+
+    // En un borde casi vertical la rejilla 2x2 solo distingue dos columnas de
+    // submuestras; la rotada tiene cuatro y escalona más fino
+    #[test]
+    fn rotated_grid_gives_more_edge_levels_than_grid() {
+        let grid = silhouette_levels(SamplePattern::Grid);
+        let rotated = silhouette_levels(SamplePattern::RotatedGrid);
+        assert!(rotated.len() > grid.len(), "grid {grid:?}, rotada {rotated:?}");
+    }
+
+    #[test]
+    fn grid_patterns_honour_non_square_sample_counts() {
+        for pattern in [SamplePattern::Grid, SamplePattern::RotatedGrid] {
+            for samples in [2, 4, 8, 9, 12] {
+                let offsets = sample_offsets(pattern, samples);
+                assert_eq!(offsets.len(), samples as usize, "{pattern:?}");
+                assert!(offsets.iter().all(|&(x, y)| (0.0..1.0).contains(&x) && (0.0..1.0).contains(&y)));
+            }
+        }
+    }
+
+    // Pase de reflexión de una esfera metálica rugosa frente a una pared a
+    // cuadros detrás de la cámara, a 1 muestra por píxel: el único azar es el
+    // jitter del lóbulo brillante
+    fn rough_metal_reflection() -> Vec<f32> {
+        let checker = crate::texture::Texture::Checker {
+            color1: Color::new(255.0, 255.0, 255.0),
+            color2: Color::new(40.0, 40.0, 40.0),
+            scale: 40.0,
+            rotation: 0.0,
+        };
+        let objects: Vec<Box<dyn RayIntersect>> = vec![
+            Box::new(crate::cube::Cube::new(
+                Vec3::new(0.0, 0.0, 20.0),
+                20.0,
+                Material::new(Color::new(255.0, 255.0, 255.0), 0.0, [1.0, 0.0]).with_texture(checker),
+            )),
+            Box::new(Sphere::new(Vec3::zeros(), 1.0, Material::metal(Color::new(255.0, 255.0, 255.0), 0.6))),
+        ];
+        let lights = [Light::new(Vec3::new(0.0, 0.0, 8.0), Color::new(255.0, 255.0, 255.0), 1.0)];
+        let settings = RenderSettings {
+            environment: false,
+            srgb_output: false,
+            components: ComponentMask::REFLECTION,
+            ..RenderSettings::default()
+        };
+        let camera = Camera::new(Vec3::new(0.0, 0.0, 3.0), Vec3::zeros(), Vec3::y());
+        let (width, height) = (48, 48);
+        let mut buffer = vec![0; width * height];
+        render_into(&mut buffer, width, height, &objects, &camera, &lights, &settings);
+        buffer.iter().map(|&px| (px & 0xFF) as f32).collect()
+    }
+
+    // El ruido entre dos renders de la esfera no debe repetirse entre píxeles
+    // vecinos: si el jitter se compartiera a lo largo de la fila, el ruido
+    // sería el mismo en bandas
+    #[test]
+    fn rough_metal_glossy_noise_is_decorrelated() {
+        let (a, b) = (rough_metal_reflection(), rough_metal_reflection());
+        // pares de vecinos en la misma fila, ambos sobre la esfera
+        let mut pairs = Vec::new();
+        for y in 0..48 {
+            for x in 0..47 {
+                let (i, j) = (y * 48 + x, y * 48 + x + 1);
+                if a[i] > 0.0 && b[i] > 0.0 && a[j] > 0.0 && b[j] > 0.0 {
+                    pairs.push((a[i] - b[i], a[j] - b[j]));
+                }
+            }
+        }
+        let noisy = pairs.iter().filter(|(n, _)| *n != 0.0).count();
+        assert!(pairs.len() > 100 && noisy > pairs.len() / 2, "{noisy} de {} píxeles con ruido", pairs.len());
+
+        let n = pairs.len() as f32;
+        let mean = |f: fn(&(f32, f32)) -> f32| pairs.iter().map(f).sum::<f32>() / n;
+        let (ma, mb) = (mean(|p| p.0), mean(|p| p.1));
+        let cov = pairs.iter().map(|(x, y)| (x - ma) * (y - mb)).sum::<f32>() / n;
+        let var_a = pairs.iter().map(|(x, _)| (x - ma).powi(2)).sum::<f32>() / n;
+        let var_b = pairs.iter().map(|(_, y)| (y - mb).powi(2)).sum::<f32>() / n;
+        let correlation = cov / (var_a * var_b).sqrt();
+        assert!(correlation.abs() < 0.4, "ruido correlado entre vecinos: {correlation}");
+    }
+
+    fn floor_and_bulb(bulb: Material) -> Vec<Box<dyn RayIntersect>> {
+        vec![
+            Box::new(crate::cube::Cube::new(Vec3::new(0.0, -6.0, 0.0), 10.0, Material::black())),
+            Box::new(Sphere::new(Vec3::new(0.0, 3.0, 0.0), 0.5, bulb)),
+        ]
+    }
+
+    // Una luz dentro de su propia geometría emisiva (una bombilla) no debe
+    // quedar tapada por ella; la misma esfera sin emisión sí hace sombra
+    #[test]
+    fn light_inside_emissive_geometry_does_not_shadow() {
+        let settings = RenderSettings::default();
+        let light = Light::new(Vec3::new(0.0, 3.0, 0.0), Color::new(255.0, 255.0, 255.0), 1.0);
+        let bulb = Material::black().with_emission(Color::new(255.0, 255.0, 255.0));
+
+        let objects = floor_and_bulb(bulb);
+        let hit = trace_closest(&Vec3::new(0.0, 1.0, 0.0), &-Vec3::y(), &objects).unwrap();
+        assert_eq!(cast_shadow(&hit, &light, &objects, &settings).g, 255.0);
+
+        let objects = floor_and_bulb(Material::black());
+        let hit = trace_closest(&Vec3::new(0.0, 1.0, 0.0), &-Vec3::y(), &objects).unwrap();
+        assert!(cast_shadow(&hit, &light, &objects, &settings).g < 255.0);
+    }
+
+    // El objeto sombreado sigue contando como oclusor: la cara de una esfera
+    // opuesta a la luz queda en sombra, la que la mira no
+    #[test]
+    fn shaded_object_shadows_itself_but_not_its_lit_side() {
+        let settings = RenderSettings::default();
+        let objects: Vec<Box<dyn RayIntersect>> = vec![Box::new(Sphere::new(Vec3::zeros(), 1.0, Material::black()))];
+        let light = Light::new(Vec3::new(0.0, 5.0, 0.0), Color::new(255.0, 255.0, 255.0), 1.0);
+
+        let bottom = trace_closest(&Vec3::new(0.0, -5.0, 0.0), &Vec3::y(), &objects).unwrap();
+        assert!(cast_shadow(&bottom, &light, &objects, &settings).g < 255.0);
+        let top = trace_closest(&Vec3::new(0.0, 5.0, 0.0), &-Vec3::y(), &objects).unwrap();
+        assert_eq!(cast_shadow(&top, &light, &objects, &settings).g, 255.0);
+    }
+
+    // Esfera roja emisiva cuya cara frontal queda a distancia 4 sobre fondo azul
+    fn clipped_color(near: f32, far: f32) -> Color {
+        let red = Material::black().with_emission(Color::new(255.0, 0.0, 0.0));
+        let objects: Vec<Box<dyn RayIntersect>> = vec![Box::new(Sphere::new(Vec3::zeros(), 1.0, red))];
+        let blue = Background::Solid(Color::new(0.0, 0.0, 255.0));
+        // sin el pase directo no se consulta la irradiancia del cielo
+        let settings = RenderSettings {
+            near,
+            far,
+            background: blue,
+            reflection_background: blue,
+            components: ComponentMask::EMISSION | ComponentMask::BACKGROUND,
+            ..RenderSettings::default()
+        };
+        cast_ray(&Vec3::new(0.0, 0.0, 5.0), &-Vec3::z(), &objects, &[], &settings, RayDepth::default(), None)
+    }
+
+    #[test]
+    fn far_clip_keeps_hits_inside_and_drops_hits_beyond() {
+        assert_eq!(clipped_color(0.0, 4.1).r, 255.0);
+        assert_eq!(clipped_color(0.0, 3.9).b, 255.0);
+    }
+
+    #[test]
+    fn near_clip_keeps_hits_beyond_and_drops_closer_hits() {
+        assert_eq!(clipped_color(3.9, f32::INFINITY).r, 255.0);
+        assert_eq!(clipped_color(4.1, f32::INFINITY).b, 255.0);
+    }
+
+    // Una fila suelta sale igual que esa misma fila de la imagen completa
+    #[test]
+    fn scanline_matches_full_render_row() {
+        let mut scene = Scene::new();
+        let shiny = Material::new(Color::new(200.0, 80.0, 40.0), 30.0, [0.9, 0.3]);
+        scene.objects.push(Box::new(Sphere::new(Vec3::zeros(), 1.0, shiny)));
+        scene.lights.push(Light::new(Vec3::new(2.0, 3.0, 4.0), Color::new(255.0, 255.0, 255.0), 1.0));
+        // sin entorno: nada de cielo ni irradiancia que cargar
+        let settings = RenderSettings { environment: false, ..RenderSettings::default() };
+        let camera = Camera::new(Vec3::new(0.0, 0.0, 4.0), Vec3::zeros(), Vec3::y());
+        let (width, height) = (16, 12);
+
+        let mut full = vec![0; width * height];
+        render_into(&mut full, width, height, &scene.objects, &camera, &scene.lights, &settings);
+        for y in [0, height / 2, height - 1] {
+            let mut row = vec![0; width];
+            render_scanline(&mut row, y, width, height, &scene, &camera, &settings);
+            assert_eq!(row, full[y * width..(y + 1) * width]);
+        }
+        assert!(full[(height / 2) * width..(height / 2 + 1) * width].iter().any(|&px| px != 0));
+    }
+
+    // Cámara en el origen, dentro de `container`, con una esfera verde delante.
+    // Devuelve los píxeles central y de esquina
+    fn render_from_inside(container: Box<dyn RayIntersect>) -> (Color, Color) {
+        let green = Material::black().with_emission(Color::new(0.0, 255.0, 0.0));
+        let objects: Vec<Box<dyn RayIntersect>> = vec![container, Box::new(Sphere::new(Vec3::new(0.0, 0.0, -2.0), 0.5, green))];
+        let blue = Background::Solid(Color::new(0.0, 0.0, 255.0));
+        let settings = RenderSettings {
+            background: blue,
+            components: ComponentMask::EMISSION | ComponentMask::BACKGROUND,
+            srgb_output: false,
+            ..RenderSettings::default()
+        };
+        let camera = Camera::new(Vec3::zeros(), Vec3::new(0.0, 0.0, -1.0), Vec3::y());
+        let (width, height) = (9, 9);
+        let mut buffer = vec![0; width * height];
+        render_into(&mut buffer, width, height, &objects, &camera, &[], &settings);
+        (Color::from_hex(buffer[(height / 2) * width + width / 2]), Color::from_hex(buffer[0]))
+    }
+
+    fn red_wall() -> Material {
+        Material::black().with_emission(Color::new(255.0, 0.0, 0.0))
+    }
+
+    #[test]
+    fn camera_inside_opaque_sphere_sees_through_it() {
+        let (center, corner) = render_from_inside(Box::new(Sphere::new(Vec3::zeros(), 5.0, red_wall())));
+        assert_eq!((center.r, center.g), (0.0, 255.0));
+        assert_eq!((corner.r, corner.b), (0.0, 255.0));
+    }
+
+    #[test]
+    fn camera_inside_opaque_cube_sees_through_it() {
+        let (center, corner) = render_from_inside(Box::new(crate::cube::Cube::new(Vec3::zeros(), 10.0, red_wall())));
+        assert_eq!((center.r, center.g), (0.0, 255.0));
+        assert_eq!((corner.r, corner.b), (0.0, 255.0));
+    }
+
+    #[test]
+    fn camera_inside_transparent_sphere_sees_inner_surface() {
+        let glass = Material::dielectric(1.5, 0.0).with_emission(Color::new(255.0, 0.0, 0.0));
+        let (center, corner) = render_from_inside(Box::new(Sphere::new(Vec3::zeros(), 5.0, glass)));
+        assert_eq!(center.g, 255.0);
+        assert_eq!((corner.r, corner.b), (255.0, 0.0));
+    }
+
+    // Con render_scale 2 un borde que parte el píxel por la mitad se promedia en
+    // lineal (127.5) y luego se codifica a sRGB, no al revés
+    #[test]
+    fn render_scale_downsamples_edge_in_linear_space() {
+        let white = Material::black().with_emission(Color::new(255.0, 255.0, 255.0));
+        // triángulo en z = 0 con el borde derecho en x = -2.5: cubre la mitad
+        // izquierda del píxel izquierdo visto desde z = 5 con 90° de campo
+        let wall = crate::triangle::Triangle::new(
+            Vec3::new(-2.5, -100.0, 0.0),
+            Vec3::new(-2.5, 100.0, 0.0),
+            Vec3::new(-200.0, 0.0, 0.0),
+            white,
+        );
+        let objects: Vec<Box<dyn RayIntersect>> = vec![Box::new(wall)];
+        let settings = RenderSettings {
+            render_scale: 2,
+            background: Background::Solid(Color::black()),
+            components: ComponentMask::EMISSION | ComponentMask::BACKGROUND,
+            ..RenderSettings::default()
+        };
+        let camera = Camera::new(Vec3::new(0.0, 0.0, 5.0), Vec3::zeros(), Vec3::y()).with_fov(std::f32::consts::FRAC_PI_2);
+        let mut buffer = vec![0; 4];
+        render_into(&mut buffer, 2, 2, &objects, &camera, &[], &settings);
+
+        let half = Color::new(127.5, 127.5, 127.5);
+        assert_eq!(buffer[0], encode_pixel(half, &settings));
+        assert_eq!(buffer[2], encode_pixel(half, &settings));
+        assert_eq!(buffer[1], 0);
+    }
+
+    #[test]
+    fn occluded_segment_through_sphere() {
+        let objects: Vec<Box<dyn RayIntersect>> = vec![Box::new(Sphere::new(Vec3::zeros(), 1.0, Material::black()))];
+        assert!(occluded(&Vec3::new(0.0, 0.0, 5.0), &Vec3::new(0.0, 0.0, -5.0), &objects));
+        // en cualquier sentido
+        assert!(occluded(&Vec3::new(0.0, 0.0, -5.0), &Vec3::new(0.0, 0.0, 5.0), &objects));
+    }
+
+    #[test]
+    fn unoccluded_segments() {
+        let objects: Vec<Box<dyn RayIntersect>> = vec![Box::new(Sphere::new(Vec3::zeros(), 1.0, Material::black()))];
+        // pasa de largo
+        assert!(!occluded(&Vec3::new(2.0, 0.0, 5.0), &Vec3::new(2.0, 0.0, -5.0), &objects));
+        // termina antes de llegar
+        assert!(!occluded(&Vec3::new(0.0, 0.0, 5.0), &Vec3::new(0.0, 0.0, 2.0), &objects));
+        // la geometría de una luz no tapa
+        let bulb: Vec<Box<dyn RayIntersect>> =
+            vec![Box::new(Sphere::new(Vec3::zeros(), 1.0, Material::black().with_emission(Color::new(255.0, 255.0, 255.0))))];
+        assert!(!occluded(&Vec3::new(0.0, 0.0, 5.0), &Vec3::new(0.0, 0.0, -5.0), &bulb));
+    }
+
+    // Cuatro láminas de vidrio sin índice (ni reflejo ni desvío) seguidas: el
+    // rayo cruza 8 interfaces y solo llega al fondo si la refracción da para
+    // todas, sin depender de la profundidad de reflexión
+    fn through_glass_panes(max_reflection_depth: u32, max_refraction_depth: u32) -> Color {
+        use crate::cube::Cube;
+        use crate::transform::{Transform, Transformed};
+        let glass = Material::black().with_transparency(1.0);
+        let objects: Vec<Box<dyn RayIntersect>> = (0..4)
+            .map(|i| {
+                let pane = Transform::new(Vec3::new(0.0, 0.0, -(i as f32)), Vec3::zeros(), Vec3::new(4.0, 4.0, 0.1));
+                Box::new(Transformed::new(Cube::new(Vec3::zeros(), 1.0, glass.clone()), pane)) as Box<dyn RayIntersect>
+            })
+            .collect();
+        let settings = RenderSettings {
+            reflection_background: Background::Solid(Color::new(255.0, 255.0, 255.0)),
+            max_reflection_depth,
+            max_refraction_depth,
+            ..RenderSettings::default()
+        };
+        cast_ray(&Vec3::new(0.0, 0.0, 5.0), &-Vec3::z(), &objects, &[], &settings, RayDepth::default(), None)
+    }
+
+    #[test]
+    fn refraction_depth_limits_glass_stack() {
+        assert_eq!(through_glass_panes(3, 3).g, 0.0);
+        assert_eq!(through_glass_panes(3, 7).g, 0.0);
+        assert_eq!(through_glass_panes(3, 8).g, 255.0);
+    }
+
+    #[test]
+    fn refraction_depth_is_independent_of_reflection_depth() {
+        assert_eq!(through_glass_panes(0, 8).g, 255.0);
+    }
+
+    // Migración a `LightKind`: el museo con sus luces puntuales declaradas con
+    // radio 0 explícito se ve igual que con `Light::new`. Sin rebotes ni cielo,
+    // para que el render sea determinista y rápido
+    #[test]
+    fn demo_renders_same_with_zero_radius_lights() {
+        let scene = crate::scene::museum();
+        let settings = RenderSettings {
+            environment: false,
+            max_reflection_depth: 0,
+            max_refraction_depth: 0,
+            ..RenderSettings::default()
+        };
+        let camera = Camera::new(Vec3::new(0.0, 3.0, 12.0), Vec3::zeros(), Vec3::y());
+        let (width, height) = (48, 32);
+        let render = |lights: &[Light]| {
+            let mut buffer = vec![0; width * height];
+            render_into(&mut buffer, width, height, &scene.objects, &camera, lights, &settings);
+            buffer
+        };
+
+        let zero_radius: Vec<Light> = scene
+            .lights
+            .iter()
+            .map(|l| Light::point(l.position, l.color, l.intensity, 0.0))
+            .collect();
+        assert!(scene.lights.iter().all(|l| matches!(l.kind, LightKind::Point { radius } if radius == 0.0)));
+        assert_eq!(render(&scene.lights), render(&zero_radius));
+    }
+
+    // Fila de puntos del suelo que cruza la sombra de una esfera pequeña bajo
+    // una luz rectangular de lado `size`: cuántos quedan en la penumbra
+    fn penumbra_width(size: f32) -> usize {
+        let objects: Vec<Box<dyn RayIntersect>> =
+            vec![Box::new(Sphere::new(Vec3::new(0.0, 1.0, 0.0), 0.5, Material::black()))];
+        let settings = RenderSettings { area_light_samples: 1024, ..RenderSettings::default() };
+        let (u_edge, v_edge) = (Vec3::x() * size, Vec3::z() * size);
+        let umbra = 255.0 * 0.3;
+        (0..=60)
+            .map(|i| Vec3::new(i as f32 / 10.0 - 3.0, 0.0, 0.0))
+            .map(|p| area_shadow(&p, Vec3::new(0.0, 4.0, 0.0), u_edge, v_edge, &objects, &settings).g)
+            .filter(|&g| g > umbra + 2.0 && g < 255.0 - 2.0)
+            .count()
+    }
+
+    #[test]
+    fn larger_rect_light_softens_shadow() {
+        let small = penumbra_width(0.3);
+        let large = penumbra_width(3.0);
+        assert!(large > small, "penumbra {small} con luz pequeña, {large} con luz grande");
+        assert!(large >= 10, "penumbra de {large} puntos con luz grande");
+    }
+
+    // Los pases de `ComponentMask` se reparten el impacto de cámara: sumados
+    // dan el render completo (en lineal, antes de codificar)
+    #[test]
+    fn component_passes_sum_to_full_render() {
+        let objects: Vec<Box<dyn RayIntersect>> = vec![
+            Box::new(crate::cube::Cube::new(Vec3::new(0.0, -11.0, 0.0), 20.0, Material::new(Color::new(200.0, 200.0, 200.0), 10.0, [0.8, 0.2]))),
+            Box::new(Sphere::new(Vec3::new(-2.2, 0.0, 0.0), 1.0, Material::new(Color::new(80.0, 120.0, 255.0), 60.0, [0.8, 0.4]).with_reflectivity(0.2))),
+            Box::new(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 1.0, Material::dielectric(1.5, 0.1))),
+            Box::new(Sphere::new(Vec3::new(2.2, 0.0, 0.0), 1.0, Material::mirror())),
+            Box::new(Sphere::new(Vec3::new(0.0, 2.0, -2.0), 0.5, Material::new(Color::new(30.0, 30.0, 30.0), 10.0, [1.0, 0.0]).with_emission(Color::new(0.0, 255.0, 180.0)))),
+        ];
+        let lights = [Light::new(Vec3::new(4.0, 6.0, 6.0), Color::new(255.0, 255.0, 255.0), 1.0)];
+        let origin = Vec3::new(0.0, 1.0, 8.0);
+        let passes = [
+            ComponentMask::DIRECT,
+            ComponentMask::REFLECTION,
+            ComponentMask::REFRACTION,
+            ComponentMask::EMISSION,
+            ComponentMask::BACKGROUND,
+        ];
+        let trace = |dir: &Vec3, components: ComponentMask| {
+            let settings = RenderSettings { environment: false, components, ..RenderSettings::default() };
+            cast_ray(&origin, dir, &objects, &lights, &settings, RayDepth::default(), None)
+        };
+
+        for j in 0..12 {
+            for i in 0..16 {
+                let dir = Vec3::new(i as f32 / 15.0 - 0.5, 0.2 - j as f32 / 22.0, -1.0).normalize();
+                let full = trace(&dir, ComponentMask::ALL);
+                let sum = passes.iter().fold(Color::black(), |acc, &pass| acc + trace(&dir, pass));
+                for (a, b) in [(full.r, sum.r), (full.g, sum.g), (full.b, sum.b)] {
+                    assert!((a - b).abs() < 1e-2, "({i}, {j}): {full:?} vs {sum:?}");
+                }
+            }
+        }
+    }
 }
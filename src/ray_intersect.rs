@@ -1,49 +1,181 @@
 use nalgebra_glm::Vec3;
+use crate::aabb::Aabb;
+use crate::light::Light;
 use crate::material::Material;
+use std::borrow::Cow;
+use std::sync::Arc;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 #[allow(dead_code)]
-pub struct Intersect {
+pub struct Intersect<'a> {
     pub point: Vec3,
     pub normal: Vec3,
     pub distance: f32,
     pub is_intersecting: bool,
-    pub material: Material,
+    // Prestado del objeto golpeado; propio solo si el impacto lo modifica
+    // (colores por vértice, emisión del entorno...)
+    pub material: Cow<'a, Material>,
     pub uv: Option<(f32, f32)>,
+    // Dirección de crecimiento de U sobre la superficie (para brillos anisótropos)
+    pub tangent: Option<Vec3>,
+    // Índice del objeto golpeado en la escena (lo rellena el renderer)
+    pub object: Option<usize>,
+    // Variación de las UV al avanzar un píxel en x y en y (diferenciales de
+    // rayo); la rellena el renderer al sombrear si `anisotropic_textures` está activo
+    pub uv_derivatives: Option<[(f32, f32); 2]>,
+    // true si el rayo llegó por el lado exterior de la superficie. La normal
+    // guardada siempre mira hacia el rayo; la exterior es `outward_normal()`
+    pub front_face: bool,
 }
 
-impl Intersect {
-    pub fn new(point: Vec3, normal: Vec3, distance: f32, material: Material) -> Self {
+impl<'a> Intersect<'a> {
+    pub fn new(point: Vec3, normal: Vec3, distance: f32, material: impl Into<Cow<'a, Material>>) -> Self {
         Intersect {
             point,
             normal,
             distance,
             is_intersecting: true,
-            material,
+            material: material.into(),
             uv: None,
+            tangent: None,
+            object: None,
+            uv_derivatives: None,
+            front_face: true,
         }
     }
 
+    // Orienta la normal contra el rayo a partir de la normal exterior y anota
+    // por qué lado se golpeó
+    pub fn with_face(mut self, ray_direction: &Vec3) -> Self {
+        self.front_face = ray_direction.dot(&self.normal) <= 0.0;
+        if !self.front_face {
+            self.normal = -self.normal;
+        }
+        self
+    }
+
+    pub fn outward_normal(&self) -> Vec3 {
+        if self.front_face { self.normal } else { -self.normal }
+    }
+
     pub fn with_uv(mut self, u: f32, v: f32) -> Self {
         self.uv = Some((u, v));
         self
     }
 
+    pub fn with_tangent(mut self, tangent: Vec3) -> Self {
+        self.tangent = Some(tangent);
+        self
+    }
+
     pub fn empty() -> Self {
         Intersect {
             point: Vec3::zeros(),
             normal: Vec3::zeros(),
             distance: 0.0,
             is_intersecting: false,
-            material: Material::black(),
+            material: Cow::Owned(Material::black()),
             uv: None,
+            tangent: None,
+            object: None,
+            uv_derivatives: None,
+            front_face: true,
         }
     }
 }
 
+// Tipo de primitiva, para herramientas que recorren la escena
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PrimitiveKind {
+  Sphere,
+  Cube,
+  Triangle,
+  Mesh,
+  Light,
+  Environment,
+  Other,
+}
+
 pub trait RayIntersect: Sync {
-  fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect;
+  fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect<'_>;
+
+  // Por defecto sin límites, así un objeto sin caja nunca se descarta
+  fn bounding_box(&self) -> Aabb {
+    Aabb::infinite()
+  }
+
+  // Geometría que representa una luz: no proyecta sombras
+  fn is_light(&self) -> bool {
+    false
+  }
+
+  // Si bloquea rayos de sombra. Las luces no; el entorno (que envuelve la
+  // escena) tampoco, o taparía todas las luces lejanas y direccionales
+  fn casts_shadows(&self) -> bool {
+    !self.is_light()
+  }
+
+  // Puntos de muestreo de la superficie emisiva que actúan como luces
+  fn emissive_lights(&self) -> Vec<Light> {
+    Vec::new()
+  }
+
+  fn kind(&self) -> PrimitiveKind {
+    PrimitiveKind::Other
+  }
+
+  // Para caras planas: distancia desde `point` (sobre la superficie) a la arista
+  // más cercana de su cara y dirección hacia ella, dentro del plano de la cara
+  fn edge_distance(&self, _point: &Vec3) -> Option<(f32, Vec3)> {
+    None
+  }
+
+  // Material principal del objeto (negro si no tiene uno propio)
+  fn material(&self) -> Material {
+    Material::black()
+  }
 }
 
+// Reenvía todo el trait al objeto apuntado: permite envolver objetos ya en
+// caja (p. ej. con `Transformed`) y compartir geometría con `Arc` (`Instance`)
+macro_rules! forward_ray_intersect {
+  ($pointer:ident, $($bound:tt)+) => {
+    impl<T: RayIntersect + $($bound)+> RayIntersect for $pointer<T> {
+      fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect<'_> {
+        self.as_ref().ray_intersect(ray_origin, ray_direction)
+      }
+
+      fn bounding_box(&self) -> Aabb {
+        self.as_ref().bounding_box()
+      }
+
+      fn is_light(&self) -> bool {
+        self.as_ref().is_light()
+      }
 
+      fn casts_shadows(&self) -> bool {
+        self.as_ref().casts_shadows()
+      }
+
+      fn emissive_lights(&self) -> Vec<Light> {
+        self.as_ref().emissive_lights()
+      }
+
+      fn kind(&self) -> PrimitiveKind {
+        self.as_ref().kind()
+      }
+
+      fn edge_distance(&self, point: &Vec3) -> Option<(f32, Vec3)> {
+        self.as_ref().edge_distance(point)
+      }
+
+      fn material(&self) -> Material {
+        self.as_ref().material()
+      }
+    }
+  };
+}
 
+forward_ray_intersect!(Box, ?Sized);
+// Arc<T> solo es Sync si T también es Send
+forward_ray_intersect!(Arc, Send + ?Sized);
@@ -0,0 +1,298 @@
+use nalgebra_glm::Vec3;
+use std::f32::consts::PI;
+use std::sync::Arc;
+use crate::aabb::Aabb;
+use crate::color::Color;
+use crate::cube::Cube;
+use crate::light::Light;
+use crate::material::Material;
+use crate::skybox::SkyboxConfig;
+use crate::ray_intersect::{RayIntersect, PrimitiveKind};
+use crate::sphere::Sphere;
+use crate::texture::{Texture, register_image};
+use crate::transform::{Transform, Transformed};
+
+// Problemas detectados por `Scene::validate`; los índices son de `lights` u `objects`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Warning {
+    // Luz con intensidad 0 (o negativa): no aporta nada
+    DarkLight(usize),
+    // Luz con posición o intensidad NaN/infinita
+    NonFiniteLight(usize),
+    // reflectividad + transparencia > 1: el material crea energía
+    EnergyGain { object: usize, sum: f32 },
+    // Caja envolvente con NaN (posición o tamaño inválidos)
+    NonFiniteObject(usize),
+    // Objeto de tamaño nulo en los tres ejes: nunca se verá
+    ZeroSizeObject(usize),
+}
+
+// Identificador estable de un objeto añadido con `Scene::add`: sigue siendo
+// válido aunque se quiten otros objetos (su índice en `objects` sí cambia)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObjectId(usize);
+
+#[derive(Default)]
+pub struct Scene {
+    pub objects: Vec<Box<dyn RayIntersect>>,
+    pub lights: Vec<Light>,
+    // Cielo propio de la escena; None deja el que esté activo
+    pub skybox: Option<SkyboxConfig>,
+    // Índice actual en `objects` de cada id entregado (None = quitado). Los
+    // objetos metidos directamente en `objects` no tienen id
+    slots: Vec<Option<usize>>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene::default()
+    }
+
+    pub fn add(&mut self, object: Box<dyn RayIntersect>) -> ObjectId {
+        self.objects.push(object);
+        self.slots.push(Some(self.objects.len() - 1));
+        ObjectId(self.slots.len() - 1)
+    }
+
+    // Quita el objeto y devuelve su caja; los ids del resto siguen valiendo.
+    // El renderer prepara la escena en cada frame, así que no hay nada más que rehacer
+    pub fn remove(&mut self, id: ObjectId) -> Option<Box<dyn RayIntersect>> {
+        let index = self.slots.get_mut(id.0)?.take()?;
+        for slot in self.slots.iter_mut().flatten() {
+            if *slot > index {
+                *slot -= 1;
+            }
+        }
+        Some(self.objects.remove(index))
+    }
+
+    // Índice actual del objeto en `objects` (p. ej. para cruzarlo con el buffer de ids)
+    pub fn index_of(&self, id: ObjectId) -> Option<usize> {
+        self.slots.get(id.0).copied().flatten()
+    }
+
+    pub fn get(&self, id: ObjectId) -> Option<&dyn RayIntersect> {
+        self.index_of(id).map(|i| self.objects[i].as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: ObjectId) -> Option<&mut Box<dyn RayIntersect>> {
+        self.index_of(id).map(|i| &mut self.objects[i])
+    }
+
+    // Activa el cielo de la escena, si tiene uno. Devuelve false solo si la
+    // imagen configurada no se pudo cargar
+    pub fn apply_skybox(&self) -> bool {
+        self.skybox.as_ref().is_none_or(|sky| sky.apply())
+    }
+
+    // Recorre los objetos de la escena (para conteos, selección, exportar...)
+    pub fn primitives(&self) -> impl Iterator<Item = &dyn RayIntersect> {
+        self.objects.iter().map(|obj| obj.as_ref())
+    }
+
+    pub fn count(&self, kind: PrimitiveKind) -> usize {
+        self.primitives().filter(|obj| obj.kind() == kind).count()
+    }
+
+    // Unión de las cajas de todos los objetos; los que no tienen límites se ignoran
+    pub fn bounds(&self) -> Aabb {
+        self.objects
+            .iter()
+            .map(|obj| obj.bounding_box())
+            .filter(|b| b.is_finite())
+            .fold(Aabb::empty(), |acc, b| acc.union(&b))
+    }
+
+    // Revisión rápida antes de renderizar (p. ej. de escenas cargadas de archivo)
+    pub fn validate(&self) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+
+        for (i, light) in self.lights.iter().enumerate() {
+            let finite = light.position.iter().all(|c| c.is_finite()) && light.intensity.is_finite();
+            if !finite {
+                warnings.push(Warning::NonFiniteLight(i));
+            } else if light.intensity <= 0.0 {
+                warnings.push(Warning::DarkLight(i));
+            }
+        }
+
+        for (i, obj) in self.objects.iter().enumerate() {
+            let material = obj.material();
+            let sum = material.reflectivity + material.transparency;
+            if sum > 1.0 {
+                warnings.push(Warning::EnergyGain { object: i, sum });
+            }
+
+            let bounds = obj.bounding_box();
+            if bounds.min.iter().chain(bounds.max.iter()).any(|c| c.is_nan()) {
+                warnings.push(Warning::NonFiniteObject(i));
+            } else if bounds.is_finite() && bounds.max == bounds.min {
+                warnings.push(Warning::ZeroSizeObject(i));
+            }
+        }
+
+        warnings
+    }
+
+    // Coloca `rows` x `cols` objetos en una rejilla sobre el plano XZ centrada en
+    // el origen; `make_object(fila, columna)` crea cada objeto alrededor del origen
+    pub fn add_grid(
+        &mut self,
+        rows: usize,
+        cols: usize,
+        spacing: f32,
+        make_object: impl Fn(usize, usize) -> Box<dyn RayIntersect>,
+    ) {
+        let x0 = cols.saturating_sub(1) as f32 * spacing * 0.5;
+        let z0 = rows.saturating_sub(1) as f32 * spacing * 0.5;
+        for row in 0..rows {
+            for col in 0..cols {
+                let offset = Vec3::new(col as f32 * spacing - x0, 0.0, row as f32 * spacing - z0);
+                let transform = Transform::new(offset, Vec3::zeros(), Vec3::new(1.0, 1.0, 1.0));
+                self.objects.push(Box::new(Transformed::new(make_object(row, col), transform)));
+            }
+        }
+    }
+}
+
+// Escena de demostración: anillo de pedestales con una esfera de cada material,
+// suelo de damero y dos luces puntuales
+pub fn museum() -> Scene {
+    // --- Materiales base ---
+    let marble = Material::new(Color::from_srgb(220.0, 220.0, 230.0), 20.0, [0.8, 0.2]);
+    let _gold = Material::new(Color::from_srgb(255.0, 215.0, 0.0), 80.0, [0.7, 0.3]);
+    let _copper = Material::new(Color::from_srgb(184.0, 115.0, 51.0), 50.0, [0.7, 0.3]);
+
+    // Registrar textura de mármol para pedestales
+    let _ = register_image(1, "src/assets/marmol.jpg");
+
+    // --- Objetos ---
+    let mut scene = Scene::new();
+
+    // Suelo con textura checker
+    // casillas de 5 unidades de mundo; la cara superior queda a la altura de la
+    // base de los pedestales (y = -1) y la cámara ya no arranca dentro del cubo
+    let floor_size = 50.0;
+    scene.objects.push(Box::new(Cube::new(
+        Vec3::new(0.0, -1.0 - floor_size / 2.0, 0.0),
+        floor_size,
+        Material::new(Color::from_srgb(245.0, 245.0, 245.0), 10.0, [0.8, 0.2])
+            .with_texture(Texture::checker_world(Color::from_srgb(240.0, 240.0, 240.0), Color::from_srgb(210.0, 210.0, 210.0), 5.0, floor_size)),
+    )));
+
+    // Disposición circular de pedestales y esferas
+    let count = 12;
+    let radius_ring = 6.0;
+
+    // Definir 12 materiales de esferas
+    let sphere_materials: Vec<Material> = vec![
+        // 1. Metal pulido
+        Material::metal(Color::from_srgb(200.0, 200.0, 200.0), 0.0),
+        // 2. Metal rugoso
+        Material::metal(Color::from_srgb(180.0, 180.0, 180.0), 1.0),
+        // 3. Plástico brillante
+        Material::new(Color::from_srgb(80.0, 120.0, 255.0), 80.0, [0.8, 0.2]).with_reflectivity(0.1),
+        // 4. Vidrio transparente
+        Material::dielectric(1.5, 0.06).with_tint(Color::from_srgb(200.0, 255.0, 255.0)),
+        // 5. Vidrio esmerilado
+        Material::dielectric(1.5, 0.06).with_tint(Color::from_srgb(220.0, 240.0, 240.0)).with_roughness(1.0),
+        // 6. Agua
+        Material::dielectric(1.33, 0.0).with_tint(Color::from_srgb(180.0, 200.0, 255.0)),
+        // 7. Mármol (procedural)
+        Material::new(Color::from_srgb(230.0, 230.0, 240.0), 30.0, [0.8, 0.2]).with_texture(Texture::MarbleProc { color1: Color::from_srgb(230.0, 230.0, 240.0), color2: Color::from_srgb(180.0, 180.0, 200.0), scale: 12.0 }),
+        // 8. Oro
+        Material::new(Color::from_srgb(255.0, 215.0, 0.0), 80.0, [0.7, 0.3]).with_reflectivity(0.8),
+        // 9. Cobre
+        Material::new(Color::from_srgb(184.0, 115.0, 51.0), 50.0, [0.7, 0.3]).with_reflectivity(0.75),
+        // 10. Neón (emisión)
+        Material::new(Color::from_srgb(30.0, 30.0, 30.0), 10.0, [1.0, 0.0]).with_emission(Color::from_srgb(0.0, 255.0, 180.0)).with_receives_shadows(false),
+        // 11. Niebla/volumen (aprox)
+        Material::new(Color::from_srgb(200.0, 200.0, 220.0), 5.0, [1.0, 0.0]).with_transparency(0.5).with_ior(1.0),
+        // 12. Espejo
+        Material::mirror(),
+    ];
+
+    // pedestal con textura de imagen marmol, un único material para todos
+    let pedestal_mat = Arc::new(marble.with_texture(Texture::Image { id: 1, scale: 2.0 }));
+
+    for i in 0..count {
+        let angle = 2.0 * PI * (i as f32) / (count as f32);
+        let px = radius_ring * angle.cos();
+        let pz = radius_ring * angle.sin();
+
+        scene.objects.push(Box::new(Cube::new(Vec3::new(px, -0.5, pz), 1.0, Arc::clone(&pedestal_mat))));
+
+        // esfera encima con material específico
+        let sphere_y = 0.8;
+        let sphere_mat = sphere_materials[i as usize % sphere_materials.len()].clone();
+        scene.objects.push(Box::new(Sphere::new(Vec3::new(px, sphere_y, pz), 0.6, sphere_mat)));
+    }
+
+    // Luces
+    scene.lights.push(Light::new(Vec3::new(5.0, 5.0, 5.0), Color::from_srgb(255.0, 255.0, 240.0), 1.2));
+    scene.lights.push(Light::new(Vec3::new(-5.0, 4.0, 2.0), Color::from_srgb(200.0, 200.0, 255.0), 0.8));
+
+    scene
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn white() -> Color {
+        Color::new(255.0, 255.0, 255.0)
+    }
+
+    fn with_light(light: Light) -> Scene {
+        let mut scene = Scene::new();
+        scene.lights.push(light);
+        scene
+    }
+
+    fn with_object(object: impl RayIntersect + 'static) -> Scene {
+        let mut scene = Scene::new();
+        scene.objects.push(Box::new(object));
+        scene
+    }
+
+    #[test]
+    fn valid_scene_has_no_warnings() {
+        let mut scene = with_object(Sphere::new(Vec3::zeros(), 1.0, Material::dielectric(1.5, 0.0)));
+        scene.lights.push(Light::new(Vec3::new(0.0, 5.0, 0.0), white(), 1.0));
+        assert!(scene.validate().is_empty());
+    }
+
+    #[test]
+    fn warns_dark_light() {
+        let scene = with_light(Light::new(Vec3::zeros(), white(), 0.0));
+        assert_eq!(scene.validate(), vec![Warning::DarkLight(0)]);
+    }
+
+    #[test]
+    fn warns_non_finite_light() {
+        let scene = with_light(Light::new(Vec3::new(f32::NAN, 0.0, 0.0), white(), 1.0));
+        assert_eq!(scene.validate(), vec![Warning::NonFiniteLight(0)]);
+        let scene = with_light(Light::new(Vec3::zeros(), white(), f32::INFINITY));
+        assert_eq!(scene.validate(), vec![Warning::NonFiniteLight(0)]);
+    }
+
+    #[test]
+    fn warns_energy_gain() {
+        let material = Material::black().with_reflectivity(0.7).with_transparency(0.6);
+        let scene = with_object(Sphere::new(Vec3::zeros(), 1.0, material));
+        let warnings = scene.validate();
+        assert!(matches!(warnings[..], [Warning::EnergyGain { object: 0, sum }] if (sum - 1.3).abs() < 1e-6));
+    }
+
+    #[test]
+    fn warns_non_finite_object() {
+        let scene = with_object(Sphere::new(Vec3::new(0.0, f32::NAN, 0.0), 1.0, Material::black()));
+        assert_eq!(scene.validate(), vec![Warning::NonFiniteObject(0)]);
+    }
+
+    #[test]
+    fn warns_zero_size_object() {
+        let scene = with_object(Sphere::new(Vec3::zeros(), 0.0, Material::black()));
+        assert_eq!(scene.validate(), vec![Warning::ZeroSizeObject(0)]);
+    }
+}
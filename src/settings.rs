@@ -0,0 +1,308 @@
+use crate::color::Color;
+use crate::light::Light;
+use crate::ray_intersect::Intersect;
+
+// Sombreado local personalizado: recibe el impacto y las luces de la escena
+pub type ShadeFn = dyn Fn(&Intersect, &[Light]) -> Color + Sync;
+
+// Distribución de las submuestras dentro de cada píxel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplePattern {
+    Random,
+    Grid,
+    RotatedGrid,
+    Halton,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    Shaded,
+    // Los rayos que no golpean nada se pintan con un damero magenta/negro
+    MissChecker,
+    // Modos de depuración del impacto primario (sin sombreado ni rebotes)
+    Normals,
+    Depth,
+    Uv,
+    ObjectId,
+}
+
+impl RenderMode {
+    // Siguiente modo, volviendo a `Shaded` después del último
+    pub fn next(self) -> Self {
+        match self {
+            RenderMode::Shaded => RenderMode::MissChecker,
+            RenderMode::MissChecker => RenderMode::Normals,
+            RenderMode::Normals => RenderMode::Depth,
+            RenderMode::Depth => RenderMode::Uv,
+            RenderMode::Uv => RenderMode::ObjectId,
+            RenderMode::ObjectId => RenderMode::Shaded,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RenderMode::Shaded => "SHADED",
+            RenderMode::MissChecker => "MISS CHECKER",
+            RenderMode::Normals => "NORMALS",
+            RenderMode::Depth => "DEPTH",
+            RenderMode::Uv => "UV",
+            RenderMode::ObjectId => "OBJECT ID",
+        }
+    }
+}
+
+// Términos del sombreado que entran en la imagen (pases tipo AOV). Solo se
+// aplican al impacto de cámara: los rayos reflejados y refractados se sombrean
+// completos, así la suma de los pases por separado da el render normal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComponentMask(u8);
+
+impl ComponentMask {
+    // Iluminación local (luces, ambiental y sombras), ponderada por 1 - r - t
+    pub const DIRECT: ComponentMask = ComponentMask(1);
+    pub const REFLECTION: ComponentMask = ComponentMask(1 << 1);
+    pub const REFRACTION: ComponentMask = ComponentMask(1 << 2);
+    pub const EMISSION: ComponentMask = ComponentMask(1 << 3);
+    // Cielo o fondo de los rayos de cámara que no golpean nada
+    pub const BACKGROUND: ComponentMask = ComponentMask(1 << 4);
+    pub const ALL: ComponentMask = ComponentMask(0b1_1111);
+    pub const NONE: ComponentMask = ComponentMask(0);
+
+    pub fn contains(self, other: ComponentMask) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    // Recorre el render completo y después cada pase suelto; una combinación
+    // hecha a mano vuelve al render completo
+    pub fn next(self) -> Self {
+        match self {
+            ComponentMask::ALL => ComponentMask::DIRECT,
+            ComponentMask::DIRECT => ComponentMask::REFLECTION,
+            ComponentMask::REFLECTION => ComponentMask::REFRACTION,
+            ComponentMask::REFRACTION => ComponentMask::EMISSION,
+            ComponentMask::EMISSION => ComponentMask::BACKGROUND,
+            _ => ComponentMask::ALL,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ComponentMask::ALL => "ALL",
+            ComponentMask::DIRECT => "DIRECT",
+            ComponentMask::REFLECTION => "REFLECTION",
+            ComponentMask::REFRACTION => "REFRACTION",
+            ComponentMask::EMISSION => "EMISSION",
+            ComponentMask::BACKGROUND => "BACKGROUND",
+            ComponentMask::NONE => "NONE",
+            _ => "CUSTOM",
+        }
+    }
+}
+
+impl std::ops::BitOr for ComponentMask {
+    type Output = ComponentMask;
+
+    fn bitor(self, rhs: ComponentMask) -> ComponentMask {
+        ComponentMask(self.0 | rhs.0)
+    }
+}
+
+// Qué se ve cuando un rayo no golpea nada
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Background {
+    Skybox,
+    Solid(Color),
+}
+
+// Combinaciones predefinidas de calidad/velocidad:
+// - Preview: 1 muestra por píxel, sombras duras, 4 muestras por luz de área
+// - Balanced: 4 muestras RGSS, bordes de sombra suaves (4 rayos) y 16 muestras de área
+// - High: 16 muestras Halton, sombras suaves con 8 rayos y 64 muestras de área
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityPreset {
+    Preview,
+    Balanced,
+    High,
+}
+
+impl QualityPreset {
+    // Siguiente preset, volviendo a `Preview` después de `High`
+    pub fn next(self) -> Self {
+        match self {
+            QualityPreset::Preview => QualityPreset::Balanced,
+            QualityPreset::Balanced => QualityPreset::High,
+            QualityPreset::High => QualityPreset::Preview,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            QualityPreset::Preview => "PREVIEW",
+            QualityPreset::Balanced => "BALANCED",
+            QualityPreset::High => "HIGH",
+        }
+    }
+}
+
+// Operador para llevar el color HDR al rango mostrable antes de codificar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMap {
+    // Recorte directo a 0-255
+    Clamp,
+    // c / (1 + c)
+    Reinhard,
+    // Aproximación de la curva filmica ACES (Narkowicz)
+    Aces,
+}
+
+#[derive(Clone, Copy)]
+pub struct RenderSettings<'a> {
+    // Si está presente, reemplaza el cálculo de iluminación local de `cast_ray`
+    pub shade_override: Option<&'a ShadeFn>,
+    // Filtrado de bordes de sombra (PCF): rayos por luz y radio de jitter alrededor de ella
+    pub shadow_taps: u32,
+    pub shadow_jitter: f32,
+    // Penumbra aproximada con un solo rayo de sombra (ver `cone_shadow`):
+    // tangente del semiángulo del cono hacia la luz; 0 = sombras duras
+    pub shadow_cone: f32,
+    // Umbral de relevancia de las luces: en cada punto se ignoran las que llegan
+    // con intensidad (ya atenuada y por el cono, por el canal más fuerte de su
+    // color) menor que esto. Sirve para escenas con muchas luces atenuadas; 0 = todas
+    pub light_cutoff: f32,
+    // Muestras por luz de área (rejilla con jitter sobre el rectángulo)
+    pub area_light_samples: u32,
+    pub mode: RenderMode,
+    // Términos que se suman en el impacto de cámara (ALL = render normal)
+    pub components: ComponentMask,
+    // Antialiasing: muestras por píxel (1 = centro del píxel) y su patrón
+    pub samples_per_pixel: u32,
+    pub sample_pattern: SamplePattern,
+    // Superresolución: cada píxel se calcula como n x n subpíxeles y se reduce
+    // en lineal (1 = resolución nativa)
+    pub render_scale: u32,
+    // Rebotes máximos de reflexión y de refracción, contados por separado: el
+    // vidrio grueso necesita muchas refracciones sin encarecer los reflejos
+    pub max_reflection_depth: u32,
+    pub max_refraction_depth: u32,
+    // Suaviza las aristas de las caras planas (cubos) estimando su cobertura del píxel
+    pub analytic_edge_aa: bool,
+    // Diferenciales de rayo (rayos vecinos que siguen al principal también en
+    // reflejos y refracciones) para estimar la huella del píxel en UV; las
+    // texturas de imagen promedian muestras a lo largo de su eje mayor
+    pub anisotropic_textures: bool,
+    // Región a renderizar (x0, y0, x1, y1), con x1/y1 exclusivos; el resto no se toca
+    pub crop: Option<(usize, usize, usize, usize)>,
+    // Distancias de recorte: se ignoran impactos más cerca que `near` o más lejos que `far`
+    pub near: f32,
+    pub far: f32,
+    // Fondo para rayos de cámara y para rayos secundarios (reflexión/refracción)
+    pub background: Background,
+    pub reflection_background: Background,
+    // false: sin entorno; los fallos devuelven negro puro y no hay luz ambiental
+    // del cielo (útil para componer de forma aditiva)
+    pub environment: bool,
+    // Giro del entorno alrededor del eje Y en radianes (fondo e irradiancia);
+    // positivo va de +X hacia +Z, como `Camera::orbit`
+    pub sky_rotation: f32,
+    // Codificar la salida a sRGB; el render trabaja en lineal (ver `Color`).
+    // Solo conviene desactivarlo para volcar valores lineales tal cual
+    pub srgb_output: bool,
+    // Exposición en pasos (el color se multiplica por 2^exposure) y curva de tono
+    pub exposure: f32,
+    pub tone_map: ToneMap,
+    // Normalizar el brillo especular por energía: (n+2)/2π en Phong y (n+8)/8π en
+    // el lóbulo anisótropo (tipo Blinn), así subir el exponente no cambia la energía total
+    pub normalized_specular: bool,
+    // Techo suave del especular sumado de todas las luces (escala 0-255 lineal,
+    // ver `soft_clamp`): evita puntos sueltos muy brillantes en metales; 0 = sin techo
+    pub specular_clamp: f32,
+    // Niebla por distancia: factor exp(-densidad * distancia) hacia `fog_color`; 0 la desactiva
+    pub fog_color: Color,
+    pub fog_density: f32,
+    // Depuración: tiñe lo que queda dentro del cono de cada foco y resalta su borde
+    pub debug_spot_cones: bool,
+    // Depuración: cuenta los píxeles con canales NaN/infinitos, los pinta de
+    // magenta y avisa por stderr al terminar el render
+    pub debug_nan: bool,
+    // Tiempo de la escena (segundos) para texturas animadas; cada submuestra
+    // lo desplaza al azar dentro de [time, time + shutter)
+    pub time: f32,
+    pub shutter: f32,
+}
+
+impl Default for RenderSettings<'_> {
+    fn default() -> Self {
+        RenderSettings {
+            shade_override: None,
+            shadow_taps: 1,
+            shadow_jitter: 0.0,
+            shadow_cone: 0.0,
+            light_cutoff: 0.0,
+            area_light_samples: 16,
+            mode: RenderMode::Shaded,
+            components: ComponentMask::ALL,
+            samples_per_pixel: 1,
+            sample_pattern: SamplePattern::RotatedGrid,
+            render_scale: 1,
+            max_reflection_depth: 3,
+            max_refraction_depth: 3,
+            analytic_edge_aa: false,
+            anisotropic_textures: false,
+            crop: None,
+            near: 0.0,
+            far: f32::INFINITY,
+            background: Background::Skybox,
+            reflection_background: Background::Skybox,
+            environment: true,
+            sky_rotation: 0.0,
+            srgb_output: true,
+            exposure: 0.0,
+            tone_map: ToneMap::Clamp,
+            normalized_specular: false,
+            specular_clamp: 0.0,
+            fog_color: Color::new(180.0, 180.0, 190.0),
+            fog_density: 0.0,
+            debug_spot_cones: false,
+            debug_nan: false,
+            time: 0.0,
+            shutter: 0.0,
+        }
+    }
+}
+
+impl<'a> RenderSettings<'a> {
+    pub fn with_shade_override(mut self, shade: &'a ShadeFn) -> Self {
+        self.shade_override = Some(shade);
+        self
+    }
+
+    pub fn with_soft_shadow_edges(mut self, taps: u32, jitter: f32) -> Self {
+        self.shadow_taps = taps;
+        self.shadow_jitter = jitter;
+        self
+    }
+
+    // Aplica un preset de calidad; el resto de opciones se conserva
+    pub fn with_quality(mut self, preset: QualityPreset) -> Self {
+        let (spp, pattern, taps, jitter, area) = match preset {
+            QualityPreset::Preview => (1, SamplePattern::RotatedGrid, 1, 0.0, 4),
+            QualityPreset::Balanced => (4, SamplePattern::RotatedGrid, 4, 0.15, 16),
+            QualityPreset::High => (16, SamplePattern::Halton, 8, 0.2, 64),
+        };
+        self.samples_per_pixel = spp;
+        self.sample_pattern = pattern;
+        self.area_light_samples = area;
+        self.with_soft_shadow_edges(taps, jitter)
+    }
+
+    pub fn with_fog(mut self, color: Color, density: f32) -> Self {
+        self.fog_color = color;
+        self.fog_density = density;
+        self
+    }
+
+    pub fn with_specular_clamp(mut self, limit: f32) -> Self {
+        self.specular_clamp = limit;
+        self
+    }
+}
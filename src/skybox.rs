@@ -1,61 +1,459 @@
 use nalgebra_glm::Vec3;
+use crate::assets;
 use crate::color::Color;
 use crate::ray_intersect::{RayIntersect, Intersect};
-use once_cell::sync::OnceCell;
-use image::DynamicImage;
+use once_cell::sync::Lazy;
+use image::{DynamicImage, Rgb32FImage, RgbImage};
+use std::collections::HashMap;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Once, RwLock};
 
 pub struct Skybox;
 
-static SKYBOX_IMG: OnceCell<Option<DynamicImage>> = OnceCell::new();
+// Las imágenes HDR (EXR) se guardan en flotante para conservar el rango dinámico;
+// las LDR se quedan en 8 bits
+enum SkyImage {
+    Hdr(Rgb32FImage),
+    Ldr(RgbImage),
+}
+
+impl SkyImage {
+    fn from_dynamic(img: DynamicImage) -> Self {
+        match img {
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => SkyImage::Hdr(img.to_rgb32f()),
+            _ => SkyImage::Ldr(img.to_rgb8()),
+        }
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        match self {
+            SkyImage::Hdr(img) => img.dimensions(),
+            SkyImage::Ldr(img) => img.dimensions(),
+        }
+    }
+
+    // Devuelve el texel lineal en la escala 0-255 de `Color` (las LDR vienen en
+    // sRGB); en HDR puede superar 255
+    fn texel(&self, x: u32, y: u32) -> Color {
+        match self {
+            SkyImage::Hdr(img) => {
+                let px = img.get_pixel(x, y);
+                Color::new(px[0] * 255.0, px[1] * 255.0, px[2] * 255.0)
+            }
+            SkyImage::Ldr(img) => {
+                let px = img.get_pixel(x, y);
+                Color::from_srgb(px[0] as f32, px[1] as f32, px[2] as f32)
+            }
+        }
+    }
+}
+
+// Mapa de irradiancia: versión de baja resolución del entorno convolucionada
+// con el coseno, para el término ambiental difuso
+const IRR_W: u32 = 32;
+const IRR_H: u32 = 16;
+// Resolución a la que se promedia la imagen antes de convolucionar
+const SRC_W: u32 = 64;
+const SRC_H: u32 = 32;
+
+struct EnvMap {
+    image: SkyImage,
+    irradiance: Vec<Color>,
+}
+
+impl EnvMap {
+    fn new(image: SkyImage) -> Self {
+        let irradiance = convolve_irradiance(&image);
+        EnvMap { image, irradiance }
+    }
+}
+
+// Dirección del centro de la celda (col, row) de una rejilla equirectangular
+// w x h, con la fila 0 en el cénit (misma convención que `sample_color`)
+fn cell_direction(col: u32, row: u32, w: u32, h: u32) -> Vec3 {
+    let u = (col as f32 + 0.5) / w as f32;
+    let v = 1.0 - (row as f32 + 0.5) / h as f32;
+    let phi = (u - 0.5) * 2.0 * std::f32::consts::PI;
+    let lat = (v - 0.5) * std::f32::consts::PI;
+    Vec3::new(lat.cos() * phi.sin(), lat.sin(), lat.cos() * phi.cos())
+}
+
+fn convolve_irradiance(image: &SkyImage) -> Vec<Color> {
+    // Promediar la imagen completa en SRC_W x SRC_H celdas
+    let (w, h) = image.dimensions();
+    let mut sums = vec![Color::black(); (SRC_W * SRC_H) as usize];
+    let mut counts = vec![0u32; (SRC_W * SRC_H) as usize];
+    for y in 0..h {
+        let row = (y * SRC_H / h).min(SRC_H - 1);
+        for x in 0..w {
+            let col = (x * SRC_W / w).min(SRC_W - 1);
+            let i = (row * SRC_W + col) as usize;
+            sums[i] = sums[i] + image.texel(x, y);
+            counts[i] += 1;
+        }
+    }
+
+    // Radiancia, dirección y ángulo sólido de cada celda
+    let cell_angle = (2.0 * std::f32::consts::PI / SRC_W as f32) * (std::f32::consts::PI / SRC_H as f32);
+    let mut cells = Vec::with_capacity(sums.len());
+    for row in 0..SRC_H {
+        for col in 0..SRC_W {
+            let i = (row * SRC_W + col) as usize;
+            if counts[i] == 0 {
+                continue;
+            }
+            let dir = cell_direction(col, row, SRC_W, SRC_H);
+            let solid_angle = cell_angle * (1.0 - dir.y * dir.y).max(0.0).sqrt();
+            cells.push((dir, sums[i] * (solid_angle / counts[i] as f32)));
+        }
+    }
+
+    // E(n) = ∫ L(w) max(n·w, 0) dw; se divide por PI para que un cielo
+    // uniforme de radiancia L devuelva L
+    let mut irradiance = Vec::with_capacity((IRR_W * IRR_H) as usize);
+    for row in 0..IRR_H {
+        for col in 0..IRR_W {
+            let n = cell_direction(col, row, IRR_W, IRR_H);
+            let mut sum = Color::black();
+            for (dir, radiance) in &cells {
+                let cos = n.dot(dir);
+                if cos > 0.0 {
+                    sum = sum + *radiance * cos;
+                }
+            }
+            irradiance.push(sum * (1.0 / std::f32::consts::PI));
+        }
+    }
+    irradiance
+}
+
+// Coordenadas (u, v) en [0, 1] de una dirección en una imagen equirectangular,
+// con v = 0 en la fila superior de la imagen
+fn equirect_uv(direction: &Vec3) -> (f32, f32) {
+    // Mapear dirección -> coords equirectangulares (u,v) desde interior de cúpula
+    let dir = direction.normalize();
+    // Usamos atan2(x, z) para alinear eje Z al frente, ajustar si fuera necesario
+    let u = 0.5 + dir.x.atan2(dir.z) / (2.0 * std::f32::consts::PI);
+    // v = 1 en el cénit, v = 0 en el nadir
+    let v = 0.5 + dir.y.asin() / std::f32::consts::PI;
+    // la fila 0 es el cénit, así que V se invierte salvo que la imagen venga volteada
+    let row_v = if Skybox::flip_v() { v } else { 1.0 - v };
+    (u, row_v)
+}
+
+// Coordenadas (x, y) del texel que corresponde a una dirección en una
+// imagen equirectangular de w x h
+fn equirect_texel(direction: &Vec3, w: u32, h: u32) -> (u32, u32) {
+    let (u, row_v) = equirect_uv(direction);
+    let x = ((u.fract() * w as f32) as u32).min(w - 1);
+    let y = ((row_v.clamp(0.0, 1.0) * h as f32) as u32).min(h - 1);
+    (x, y)
+}
+
+// Filtrado bilineal: en horizontal se envuelve (la costura u = 0/1 mezcla los
+// dos extremos de la imagen) y en vertical se recorta en los polos
+fn sample_bilinear(image: &SkyImage, direction: &Vec3) -> Color {
+    let (w, h) = image.dimensions();
+    let (u, row_v) = equirect_uv(direction);
+    let fx = u.rem_euclid(1.0) * w as f32 - 0.5;
+    let fy = (row_v.clamp(0.0, 1.0) * h as f32 - 0.5).clamp(0.0, (h - 1) as f32);
+    let tx = fx - fx.floor();
+    let ty = fy - fy.floor();
+
+    let x0 = (fx.floor() as i64).rem_euclid(w as i64) as u32;
+    let x1 = (x0 + 1) % w;
+    let y0 = fy.floor() as u32;
+    let y1 = (y0 + 1).min(h - 1);
+
+    let top = image.texel(x0, y0).blend(image.texel(x1, y0), tx);
+    let bottom = image.texel(x0, y1).blend(image.texel(x1, y1), tx);
+    top.blend(bottom, ty)
+}
+
+// Cielo procedural (ver `Skybox::set_sky`): gradiente del horizonte al cénit
+// más un lóbulo brillante alrededor del sol
+#[derive(Debug, Clone, Copy)]
+struct GradientSky {
+    top: Color,
+    horizon: Color,
+    sun_dir: Vec3,
+    sun_color: Color,
+    // Radio angular aproximado del sol en radianes; 0 = sin sol
+    sun_size: f32,
+}
+
+static GRADIENT_SKY: Lazy<RwLock<GradientSky>> = Lazy::new(|| {
+    RwLock::new(GradientSky {
+        top: Color::from_srgb(135.0, 206.0, 235.0),
+        horizon: Color::new(255.0, 255.0, 255.0),
+        sun_dir: Vec3::new(0.0, 1.0, 0.0),
+        sun_color: Color::black(),
+        sun_size: 0.0,
+    })
+});
+
+// Gradiente de fallback cuando no hay ningún entorno cargado
+fn gradient_color(direction: &Vec3) -> Color {
+    let sky = match GRADIENT_SKY.read() {
+        Ok(sky) => *sky,
+        Err(_) => return Color::black(),
+    };
+    let t = 0.5 * (direction.y + 1.0);
+    let mut color = sky.horizon.blend(sky.top, t);
+    if sky.sun_size > 0.0 {
+        // lóbulo gaussiano en el ángulo al sol: 1 - cos θ ≈ θ²/2
+        let cos = direction.normalize().dot(&sky.sun_dir);
+        let glow = (-(1.0 - cos) / (sky.sun_size * sky.sun_size)).exp();
+        color = color + sky.sun_color * glow;
+    }
+    color
+}
+
+// Id reservado para el gradiente de fallback (ningún entorno cargado)
+pub const GRADIENT_ENV: u32 = u32::MAX;
+
+// Id donde `SkyboxConfig::Image` registra la imagen del cielo de una escena
+pub const SCENE_ENV: u32 = u32::MAX - 1;
+
+// Cielo descrito por la escena (imagen o gradiente), para que un archivo de
+// escena no dependa del entorno que haya cargado el programa
+#[derive(Debug, Clone, PartialEq)]
+pub enum SkyboxConfig {
+    // Panorama equirectangular; la ruta se resuelve con `assets::resolve`
+    Image { path: String, flip_v: bool },
+    // Mismos parámetros que `Skybox::set_sky`
+    Gradient { top: Color, horizon: Color, sun_dir: Vec3, sun_color: Color, sun_size: f32 },
+}
+
+impl SkyboxConfig {
+    // Activa este cielo; false si la imagen no se pudo cargar (el cielo no cambia)
+    pub fn apply(&self) -> bool {
+        match self {
+            SkyboxConfig::Image { path, flip_v } => {
+                if !Skybox::register_env(SCENE_ENV, path) {
+                    return false;
+                }
+                Skybox::set_flip_v(*flip_v);
+                Skybox::use_env(SCENE_ENV);
+                true
+            }
+            SkyboxConfig::Gradient { top, horizon, sun_dir, sun_color, sun_size } => {
+                Skybox::set_sky(*top, *horizon, *sun_dir, *sun_color, *sun_size);
+                true
+            }
+        }
+    }
+}
+
+static ENV_REG: Lazy<RwLock<HashMap<u32, EnvMap>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+static CURRENT_ENV: AtomicU32 = AtomicU32::new(0);
+static DEFAULT_ENV: Once = Once::new();
+static FLIP_V: AtomicBool = AtomicBool::new(false);
+
+// Cielo programático: color de fondo calculado a partir de la dirección.
+// Si está activo tiene prioridad sobre los entornos registrados
+pub type SkyFn = dyn Fn(Vec3) -> Color + Send + Sync;
+static SKY_FN: Lazy<RwLock<Option<Box<SkyFn>>>> = Lazy::new(|| RwLock::new(None));
+// Evita tomar el lock en cada fallo cuando no hay función
+static HAS_SKY_FN: AtomicBool = AtomicBool::new(false);
+
+fn sample_sky_fn(direction: &Vec3) -> Option<Color> {
+    if !HAS_SKY_FN.load(Ordering::Acquire) {
+        return None;
+    }
+    let reg = SKY_FN.read().ok()?;
+    reg.as_ref().map(|f| f(direction.normalize()))
+}
+
+// Suelo opcional bajo el horizonte (ver `Skybox::set_ground_color`)
+static GROUND: Lazy<RwLock<Option<Color>>> = Lazy::new(|| RwLock::new(None));
+static HAS_GROUND: AtomicBool = AtomicBool::new(false);
+// Altura (componente y de la dirección, por debajo de 0) en la que el suelo ya cubre del todo
+const GROUND_BLEND: f32 = 0.05;
+
+fn ground_color() -> Option<Color> {
+    if !HAS_GROUND.load(Ordering::Acquire) {
+        return None;
+    }
+    *GROUND.read().ok()?
+}
+
+// Cielo sin suelo: función programática, entorno activo o gradiente
+fn sample_sky(direction: &Vec3) -> Color {
+    if let Some(c) = sample_sky_fn(direction) {
+        return c;
+    }
+    load_skybox_if_needed();
+    let reg = ENV_REG.read();
+    if let Some(env) = reg.as_ref().ok().and_then(|map| map.get(&Skybox::current_env())) {
+        return sample_bilinear(&env.image, direction);
+    }
+    gradient_color(direction)
+}
 
 fn load_skybox_if_needed() {
-    SKYBOX_IMG.get_or_init(|| {
-        // Intentar ambas rutas comunes
+    DEFAULT_ENV.call_once(|| {
+        // Intentar ambas rutas comunes; el cielo por defecto ocupa el id 0
         let candidates = [
             Path::new("assets/sky.exr"),
             Path::new("src/assets/sky.exr"),
         ];
         for p in &candidates {
-            if p.exists() {
-                if let Ok(img) = image::open(p) {
-                    return Some(img);
-                }
+            let p = assets::resolve(p);
+            if p.exists() && let Ok(img) = image::open(&p) {
+                if let Ok(mut map) = ENV_REG.write() { map.entry(0).or_insert_with(|| EnvMap::new(SkyImage::from_dynamic(img))); }
+                return;
             }
         }
-        None
     });
 }
 
 impl RayIntersect for Skybox {
-    fn ray_intersect(&self, _ray_origin: &Vec3, _ray_direction: &Vec3) -> Intersect {
+    fn ray_intersect(&self, _ray_origin: &Vec3, _ray_direction: &Vec3) -> Intersect<'_> {
         Intersect::empty()
     }
 }
 
 impl Skybox {
+    // Convención esperada: panorama equirectangular estándar, con la fila 0
+    // de la imagen en el cénit (+Y) y la última fila en el nadir (-Y).
+    // Para imágenes guardadas de abajo hacia arriba activar `flip_v`.
+    pub fn set_flip_v(flip: bool) {
+        FLIP_V.store(flip, Ordering::Relaxed);
+    }
+
+    pub fn flip_v() -> bool {
+        FLIP_V.load(Ordering::Relaxed)
+    }
+
+    pub fn register_env(id: u32, path: &str) -> bool {
+        match image::open(assets::resolve(path)) {
+            Ok(img) => {
+                if let Ok(mut map) = ENV_REG.write() { map.insert(id, EnvMap::new(SkyImage::from_dynamic(img))); return true; }
+                false
+            },
+            Err(_) => false,
+        }
+    }
+
+    // Configura el cielo de gradiente con sol y lo activa. `sun_color` puede
+    // pasar de 255 para que el sol brille en los reflejos de los metales;
+    // con `sun_size` = 0 queda solo el gradiente
+    pub fn set_sky(top: Color, horizon: Color, sun_dir: Vec3, sun_color: Color, sun_size: f32) {
+        if let Ok(mut sky) = GRADIENT_SKY.write() {
+            *sky = GradientSky {
+                top,
+                horizon,
+                sun_dir: sun_dir.normalize(),
+                sun_color,
+                sun_size: sun_size.max(0.0),
+            };
+        }
+        Skybox::use_env(GRADIENT_ENV);
+    }
+
+    // Selecciona el entorno activo; un id no registrado muestra el gradiente
+    pub fn use_env(id: u32) {
+        CURRENT_ENV.store(id, Ordering::Relaxed);
+    }
+
+    pub fn current_env() -> u32 {
+        CURRENT_ENV.load(Ordering::Relaxed)
+    }
+
+    // Pasa al siguiente entorno registrado (en orden de id) y por último al gradiente
+    pub fn cycle_env() -> u32 {
+        load_skybox_if_needed();
+        let mut ids: Vec<u32> = match ENV_REG.read() {
+            Ok(map) => map.keys().copied().collect(),
+            Err(_) => Vec::new(),
+        };
+        ids.sort_unstable();
+        ids.push(GRADIENT_ENV);
+
+        let current = Skybox::current_env();
+        let next = match ids.iter().position(|&id| id == current) {
+            Some(i) => ids[(i + 1) % ids.len()],
+            None => ids[0],
+        };
+        Skybox::use_env(next);
+        next
+    }
+
+    // Reemplaza el cielo por una función determinista de la dirección
+    // (normalizada); útil para comprobar reflejos sin cargar imágenes
+    pub fn set_function(f: Box<SkyFn>) {
+        if let Ok(mut slot) = SKY_FN.write() {
+            *slot = Some(f);
+            HAS_SKY_FN.store(true, Ordering::Release);
+        }
+    }
+
+    pub fn clear_function() {
+        if let Ok(mut slot) = SKY_FN.write() {
+            *slot = None;
+            HAS_SKY_FN.store(false, Ordering::Release);
+        }
+    }
+
+    // Sustituye el hemisferio inferior del cielo por un suelo liso en
+    // `sample_color`, para que los suelos reflectantes no reflejen cielo bajo el
+    // horizonte. La irradiancia no cambia
+    pub fn set_ground_color(color: Color) {
+        if let Ok(mut ground) = GROUND.write() {
+            *ground = Some(color);
+            HAS_GROUND.store(true, Ordering::Release);
+        }
+    }
+
+    pub fn clear_ground_color() {
+        if let Ok(mut ground) = GROUND.write() {
+            *ground = None;
+            HAS_GROUND.store(false, Ordering::Release);
+        }
+    }
+
+    // Dirección en el marco del entorno girado `yaw` radianes sobre Y: girar el
+    // cielo equivale a girar la consulta `-yaw`. La altura no cambia, así que
+    // el suelo no se ve afectado
+    pub fn unrotate(direction: &Vec3, yaw: f32) -> Vec3 {
+        if yaw == 0.0 {
+            return *direction;
+        }
+        let (sin, cos) = (-yaw).sin_cos();
+        Vec3::new(direction.x * cos - direction.z * sin, direction.y, direction.x * sin + direction.z * cos)
+    }
+
     pub fn sample_color(direction: &Vec3) -> Color {
+        let Some(ground) = ground_color() else {
+            return sample_sky(direction);
+        };
+        // transición suave de cielo a suelo justo bajo el horizonte
+        let below = -direction.normalize().y / GROUND_BLEND;
+        if below >= 1.0 {
+            return ground;
+        }
+        let t = below.max(0.0);
+        sample_sky(direction).blend(ground, t * t * (3.0 - 2.0 * t))
+    }
+
+    // Luz difusa que llega del entorno a una superficie con normal `normal`,
+    // leída del mapa de irradiancia precalculado al cargar el cielo
+    pub fn sample_irradiance(normal: &Vec3) -> Color {
+        // con un cielo programático no hay mapa precalculado: se evalúa en la normal
+        if let Some(c) = sample_sky_fn(normal) {
+            return c;
+        }
         load_skybox_if_needed();
-        if let Some(Some(img)) = SKYBOX_IMG.get() {
-            // Convertir a RGB8 para acceso consistente
-            let rgb = img.to_rgb8();
-            let (w, h) = rgb.dimensions();
-
-            // Mapear dirección -> coords equirectangulares (u,v) desde interior de cúpula
-            let dir = direction.normalize();
-            // Usamos atan2(x, z) para alinear eje Z al frente, ajustar si fuera necesario
-            let u = 0.5 + dir.x.atan2(dir.z) / (2.0 * std::f32::consts::PI);
-            let v = 0.5 + dir.y.asin() / std::f32::consts::PI; // mirando desde dentro: invertir segun necesidad
-
-            let x = ((u.fract() * w as f32) as u32).min(w - 1);
-            let y = (((1.0 - v.fract()) * h as f32) as u32).min(h - 1); // invertimos V para imagenes equirectangulares tipicas
-            let px = rgb.get_pixel(x, y);
-            return Color::new(px[0] as f32, px[1] as f32, px[2] as f32);
-        }
-        // Gradiente de fallback
-        let t = 0.5 * (direction.y + 1.0);
-        let base = Color::new(135.0, 206.0, 235.0);
-        let horizon = Color::new(255.0, 255.0, 255.0);
-        horizon.blend(base, t)
+        let reg = ENV_REG.read();
+        if let Some(env) = reg.as_ref().ok().and_then(|map| map.get(&Skybox::current_env())) {
+            let (x, y) = equirect_texel(normal, IRR_W, IRR_H);
+            return env.irradiance[(y * IRR_W + x) as usize];
+        }
+        // Sin HDR se usa el gradiente en la dirección de la normal: ya es suave,
+        // y así las caras que miran arriba reciben el azul del cielo y las de
+        // abajo el color del horizonte
+        gradient_color(normal)
     }
 }
@@ -1,30 +1,44 @@
 use nalgebra_glm::Vec3;
-use crate::ray_intersect::{RayIntersect, Intersect};
+use crate::aabb::Aabb;
+use crate::light::Light;
+use crate::ray_intersect::{RayIntersect, Intersect, PrimitiveKind};
 use crate::material::Material;
+use crate::skybox::Skybox;
+use crate::texture::Texture;
+use std::sync::Arc;
 
 pub struct Sphere {
     pub center: Vec3,
     pub radius: f32,
-    pub material: Material,
+    // Compartido: varias primitivas pueden usar el mismo material sin copiarlo
+    pub material: Arc<Material>,
+}
+
+impl Sphere {
+    pub fn new(center: Vec3, radius: f32, material: impl Into<Arc<Material>>) -> Self {
+        Sphere { center, radius, material: material.into() }
+    }
 }
 
 impl RayIntersect for Sphere {
-    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect<'_> {
+        // |o + t·d - c|² = r²  =>  a·t² - 2·b·t + c = 0, sin suponer que `d` sea
+        // unitario; `t` es el parámetro del rayo, así que punto = o + t·d
         let l = self.center - ray_origin;
-        let tca = l.dot(ray_direction);
-        if tca < 0.0 {
+        let a = ray_direction.dot(ray_direction);
+        if a <= 0.0 {
             return Intersect::empty();
         }
-
-        let d2 = l.dot(&l) - tca * tca;
-        let radius2 = self.radius * self.radius;
-        if d2 > radius2 {
+        let b = l.dot(ray_direction);
+        let c = l.dot(&l) - self.radius * self.radius;
+        let disc = b * b - a * c;
+        if disc < 0.0 {
             return Intersect::empty();
         }
 
-        let thc = (radius2 - d2).sqrt();
-        let t0 = tca - thc;
-        let t1 = tca + thc;
+        let sq = disc.sqrt();
+        let t0 = (b - sq) / a;
+        let t1 = (b + sq) / a;
 
         let t = if t0 < 0.0 { t1 } else { t0 };
         if t < 0.0 {
@@ -39,6 +53,154 @@ impl RayIntersect for Sphere {
         let u = 0.5 + dir.z.atan2(dir.x) / (2.0 * std::f32::consts::PI);
         let v = 0.5 - dir.y.asin() / std::f32::consts::PI;
 
-        Intersect::new(point, normal, t, self.material).with_uv(u, v)
+        // tangente en la dirección de la longitud (indefinida en los polos)
+        let hit = Intersect::new(point, normal, t, &*self.material).with_uv(u, v).with_face(ray_direction);
+        let tangent = Vec3::new(-dir.z, 0.0, dir.x);
+        if tangent.magnitude() > 1e-6 { hit.with_tangent(tangent.normalize()) } else { hit }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let r = Vec3::repeat(self.radius.abs());
+        Aabb::new(self.center - r, self.center + r)
+    }
+
+    fn is_light(&self) -> bool {
+        self.material.emission.is_some()
+    }
+
+    fn kind(&self) -> PrimitiveKind {
+        PrimitiveKind::Sphere
+    }
+
+    fn material(&self) -> Material {
+        (*self.material).clone()
+    }
+
+    // Seis muestras sobre la superficie (una por eje), apenas por fuera para que
+    // la propia esfera no tape su luz; la caída se escala con el radio
+    fn emissive_lights(&self) -> Vec<Light> {
+        let Some(emission) = self.material.emission else {
+            return Vec::new();
+        };
+        let axes = [
+            Vec3::x(), -Vec3::x(),
+            Vec3::y(), -Vec3::y(),
+            Vec3::z(), -Vec3::z(),
+        ];
+        let attenuation = 1.0 / (self.radius * self.radius).max(1e-4);
+        axes.iter()
+            .map(|axis| {
+                Light::new(self.center + axis * self.radius * 1.01, emission, 1.0 / axes.len() as f32)
+                    .with_mask(self.material.light_mask)
+                    .with_attenuation(attenuation)
+            })
+            .collect()
+    }
+}
+
+// Esfera de entorno: envuelve la escena y se ve desde dentro como un objeto más,
+// así el fondo y los reflejos comparten geometría y UV. Sin textura muestra el
+// skybox activo. Con ella en la escena los rayos ya no llegan al fondo por fallo.
+pub struct EnvironmentSphere {
+    pub center: Vec3,
+    pub radius: f32,
+    pub texture: Option<Texture>,
+}
+
+impl EnvironmentSphere {
+    pub fn new(center: Vec3, radius: f32) -> Self {
+        EnvironmentSphere { center, radius, texture: None }
+    }
+
+    pub fn with_texture(mut self, texture: Texture) -> Self {
+        self.texture = Some(texture);
+        self
+    }
+}
+
+impl RayIntersect for EnvironmentSphere {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect<'_> {
+        // misma ecuación que `Sphere`, válida para direcciones no unitarias
+        let l = self.center - ray_origin;
+        let a = ray_direction.dot(ray_direction);
+        if a <= 0.0 {
+            return Intersect::empty();
+        }
+        let b = l.dot(ray_direction);
+        let disc = b * b - a * (l.dot(&l) - self.radius * self.radius);
+        if disc < 0.0 {
+            return Intersect::empty();
+        }
+
+        // solo la cara interior: desde dentro es la salida del rayo
+        let t = (b + disc.sqrt()) / a;
+        if t <= 0.0 {
+            return Intersect::empty();
+        }
+
+        let point = ray_origin + ray_direction * t;
+        let dir = (point - self.center).normalize();
+
+        // UV equirectangulares con la misma convención que el skybox (v = 0 en el cénit)
+        let u = 0.5 + dir.x.atan2(dir.z) / (2.0 * std::f32::consts::PI);
+        let v = 0.5 - dir.y.clamp(-1.0, 1.0).asin() / std::f32::consts::PI;
+
+        let emission = match &self.texture {
+            Some(tex) => tex.sample(u, v),
+            None => Skybox::sample_color(&dir),
+        };
+        let material = self.material().with_emission(emission);
+        // normal hacia dentro, hacia el observador: su cara frontal es la interior
+        Intersect::new(point, -dir, t, material).with_uv(u, v)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let r = Vec3::repeat(self.radius.abs());
+        Aabb::new(self.center - r, self.center + r)
+    }
+
+    fn kind(&self) -> PrimitiveKind {
+        PrimitiveKind::Environment
+    }
+
+    fn casts_shadows(&self) -> bool {
+        false
+    }
+
+    // El entorno no recibe luz: solo aporta su emisión
+    fn material(&self) -> Material {
+        Material::black().with_ambient(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    // Con una dirección no unitaria la distancia es el parámetro del rayo:
+    // el punto de impacto es el mismo y distancia · |d| la longitud recorrida
+    #[test]
+    fn non_unit_direction_hits_the_same_point() {
+        let sphere = Sphere::new(Vec3::zeros(), 1.0, Material::black());
+        let origin = Vec3::new(0.3, 0.2, 5.0);
+        let unit = sphere.ray_intersect(&origin, &-Vec3::z());
+        let scaled = sphere.ray_intersect(&origin, &(-Vec3::z() * 2.5));
+        assert!(unit.is_intersecting && scaled.is_intersecting);
+        assert!((unit.point - scaled.point).magnitude() < 1e-5);
+        assert!((scaled.distance * 2.5 - unit.distance).abs() < 1e-5);
+        assert!((unit.normal - scaled.normal).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn non_unit_direction_hits_environment_sphere_from_inside() {
+        let env = EnvironmentSphere::new(Vec3::zeros(), 10.0).with_texture(Texture::MarbleProc {
+            color1: Color::black(),
+            color2: Color::black(),
+            scale: 1.0,
+        });
+        let hit = env.ray_intersect(&Vec3::zeros(), &(Vec3::x() * 0.5));
+        assert!((hit.point - Vec3::new(10.0, 0.0, 0.0)).magnitude() < 1e-4);
+        assert!((hit.distance - 20.0).abs() < 1e-4);
     }
 }
@@ -1,64 +1,253 @@
+use crate::assets;
 use crate::color::Color;
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 use image::{DynamicImage, GenericImageView};
 
-static IMAGE_REG: Lazy<RwLock<HashMap<u32, DynamicImage>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+static IMAGE_REG: Lazy<RwLock<HashMap<u32, (DynamicImage, ColorSpace)>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+// Cómo interpretar los texels de una imagen: los mapas de color vienen en sRGB,
+// los de datos (normales, rugosidad...) ya son lineales y se leen tal cual
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+    Linear,
+}
+
+// Límite de muestras del filtrado anisótropo por píxel
+const MAX_ANISO_TAPS: u32 = 16;
 
 #[derive(Debug, Clone, Copy)]
 pub enum Axis { U, V }
 
-#[derive(Debug, Clone, Copy)]
+// Cómo `Texture::Blend` combina la capa `b` sobre la base `a`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    Multiply,
+    Add,
+    // Solo la capa `b`: con `factor` queda un fundido lineal entre ambas
+    Mix,
+    // Multiply en los oscuros de `a` y screen en los claros (contraste)
+    Overlay,
+}
+
+#[derive(Debug, Clone)]
 pub enum Texture {
-    Checker { color1: Color, color2: Color, scale: f32 },
-    Stripes { color1: Color, color2: Color, scale: f32, axis: Axis },
+    // `rotation` (radianes) gira el patrón sobre el plano UV alrededor del origen
+    Checker { color1: Color, color2: Color, scale: f32, rotation: f32 },
+    Stripes { color1: Color, color2: Color, scale: f32, axis: Axis, rotation: f32 },
     MarbleProc { color1: Color, color2: Color, scale: f32 },
     Image { id: u32, scale: f32 },
+    // Capa `b` combinada sobre `a` según `mode`; `factor` es la opacidad de la
+    // capa (0 = solo `a`, 1 = combinación completa). Las hijas se comparten con
+    // `Arc`, así clonar el material no copia el árbol
+    Blend { a: Arc<Texture>, b: Arc<Texture>, mode: BlendMode, factor: f32 },
+}
+
+// Datos extra para muestrear texturas que dependen de algo más que las UV
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SampleContext {
+    // Tiempo en segundos, para patrones procedurales animados
+    pub time: f32,
+    // Huella del píxel en UV: derivadas (du, dv) por píxel en x y en y
+    pub footprint: Option<[(f32, f32); 2]>,
 }
 
 pub fn register_image(id: u32, path: &str) -> bool {
-    match image::open(path) {
+    register_image_with(id, path, ColorSpace::Srgb)
+}
+
+pub fn register_image_with(id: u32, path: &str, color_space: ColorSpace) -> bool {
+    match image::open(assets::resolve(path)) {
         Ok(img) => {
-            if let Ok(mut map) = IMAGE_REG.write() { map.insert(id, img); return true; }
+            if let Ok(mut map) = IMAGE_REG.write() { map.insert(id, (img, color_space)); return true; }
             false
         },
         Err(_) => false,
     }
 }
 
+fn blend_channel(a: f32, b: f32, mode: BlendMode) -> f32 {
+    match mode {
+        BlendMode::Multiply => a * b / 255.0,
+        BlendMode::Add => a + b,
+        BlendMode::Mix => b,
+        BlendMode::Overlay => {
+            let (a, b) = (a / 255.0, b / 255.0);
+            let o = if a < 0.5 { 2.0 * a * b } else { 1.0 - 2.0 * (1.0 - a) * (1.0 - b) };
+            o * 255.0
+        }
+    }
+}
+
+fn rotate_uv(u: f32, v: f32, angle: f32) -> (f32, f32) {
+    if angle == 0.0 {
+        return (u, v);
+    }
+    let (sin, cos) = angle.sin_cos();
+    (u * cos - v * sin, u * sin + v * cos)
+}
+
 impl Texture {
+    // Damero con casillas de `check_size` unidades de mundo sobre una cara de
+    // `extent` unidades (las UV de las primitivas van de 0 a 1 por cara)
+    pub fn checker_world(color1: Color, color2: Color, check_size: f32, extent: f32) -> Texture {
+        let scale = if check_size > 0.0 { extent / check_size } else { 1.0 };
+        Texture::Checker { color1, color2, scale, rotation: 0.0 }
+    }
+
+    // Nodo que combina `b` sobre `a`, p. ej. un damero multiplicado sobre mármol
+    pub fn combine(a: Texture, b: Texture, mode: BlendMode, factor: f32) -> Texture {
+        Texture::Blend { a: Arc::new(a), b: Arc::new(b), mode, factor }
+    }
+
+    // Devuelve colores lineales: los colores de los patrones ya lo son (ver
+    // `Color`) y los texels de imagen se decodifican según su `ColorSpace`
     pub fn sample(&self, u: f32, v: f32) -> Color {
+        self.sample_with(u, v, &SampleContext::default())
+    }
+
+    // Como `sample`, pero las rayas y el mármol se desplazan con `ctx.time`
+    pub fn sample_with(&self, u: f32, v: f32, ctx: &SampleContext) -> Color {
         match *self {
-            Texture::Checker { color1, color2, scale } => {
+            Texture::Checker { color1, color2, scale, rotation } => {
+                let (u, v) = rotate_uv(u, v, rotation);
                 let s = (u * scale).floor() as i32 + (v * scale).floor() as i32;
                 if s % 2 == 0 { color1 } else { color2 }
             }
-            Texture::Stripes { color1, color2, scale, axis } => {
+            Texture::Stripes { color1, color2, scale, axis, rotation } => {
+                let (u, v) = rotate_uv(u, v, rotation);
                 let t = match axis { Axis::U => u, Axis::V => v };
-                if ((t * scale).floor() as i32) % 2 == 0 { color1 } else { color2 }
+                if ((t * scale + ctx.time).floor() as i32) % 2 == 0 { color1 } else { color2 }
             }
             Texture::MarbleProc { color1, color2, scale } => {
                 // Patrón simple de mármol usando senoides combinadas
-                let s = ((u * scale).sin() + (v * scale * 1.5).sin()) * 0.5;
+                let s = ((u * scale + ctx.time).sin() + (v * scale * 1.5 - ctx.time * 0.7).sin()) * 0.5;
                 let t = 0.5 * (s + 1.0);
                 color1.blend(color2, t)
             }
             Texture::Image { id, scale } => {
-                if let Ok(map) = IMAGE_REG.read() {
-                    if let Some(img) = map.get(&id) {
-                        let (w, h) = img.dimensions();
-                        let uu = (u * scale).fract();
-                        let vv = (v * scale).fract();
-                        let x = ((uu * w as f32) as u32).min(w - 1);
-                        let y = ((vv * h as f32) as u32).min(h - 1);
+                if let Ok(map) = IMAGE_REG.read() && let Some((img, color_space)) = map.get(&id) {
+                    let (w, h) = img.dimensions();
+                    let uu = (u * scale).fract();
+                    let vv = (v * scale).fract();
+                    let texel = |uu: f32, vv: f32| {
+                        let x = ((uu.rem_euclid(1.0) * w as f32) as u32).min(w - 1);
+                        let y = ((vv.rem_euclid(1.0) * h as f32) as u32).min(h - 1);
                         let px = img.get_pixel(x, y);
-                        return Color::new(px[0] as f32, px[1] as f32, px[2] as f32);
+                        match color_space {
+                            ColorSpace::Srgb => Color::from_srgb(px[0] as f32, px[1] as f32, px[2] as f32),
+                            ColorSpace::Linear => Color::new(px[0] as f32, px[1] as f32, px[2] as f32),
+                        }
+                    };
+                    let Some([dx, dy]) = ctx.footprint else {
+                        return texel(uu, vv);
+                    };
+
+                    // Eje mayor de la huella (en texels) y cuántas muestras caben a lo
+                    // largo de él respecto al eje menor
+                    let len = |(du, dv): (f32, f32)| ((du * scale * w as f32).powi(2) + (dv * scale * h as f32).powi(2)).sqrt();
+                    let (major, major_len, minor_len) = if len(dx) >= len(dy) { (dx, len(dx), len(dy)) } else { (dy, len(dy), len(dx)) };
+                    let taps = (major_len / minor_len.max(1.0)).ceil().clamp(1.0, MAX_ANISO_TAPS as f32) as u32;
+                    let mut sum = Color::black();
+                    for i in 0..taps {
+                        let t = (i as f32 + 0.5) / taps as f32 - 0.5;
+                        sum = sum + texel(uu + major.0 * scale * t, vv + major.1 * scale * t);
                     }
+                    return sum * (1.0 / taps as f32);
                 }
                 // Fallback si no está registrada la imagen
-                Color::new(200.0, 200.0, 200.0)
+                Color::from_srgb(200.0, 200.0, 200.0)
+            }
+            Texture::Blend { ref a, ref b, mode, factor } => {
+                let base = a.sample_with(u, v, ctx);
+                let layer = b.sample_with(u, v, ctx);
+                let combined = Color::new(
+                    blend_channel(base.r, layer.r, mode),
+                    blend_channel(base.g, layer.g, mode),
+                    blend_channel(base.b, layer.b, mode),
+                );
+                base.blend(combined, factor)
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flat(c: f32) -> Texture {
+        Texture::Checker { color1: Color::new(c, c, c), color2: Color::new(c, c, c), scale: 1.0, rotation: 0.0 }
+    }
+
+    fn blend(a: f32, b: f32, mode: BlendMode, factor: f32) -> f32 {
+        Texture::combine(flat(a), flat(b), mode, factor).sample(0.25, 0.25).r
+    }
+
+    #[test]
+    fn multiply_scales_base_by_layer() {
+        assert!((blend(200.0, 127.5, BlendMode::Multiply, 1.0) - 100.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn add_sums_both_layers() {
+        assert!((blend(100.0, 50.0, BlendMode::Add, 1.0) - 150.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn mix_fades_linearly_with_factor() {
+        assert!((blend(100.0, 200.0, BlendMode::Mix, 0.25) - 125.0).abs() < 1e-3);
+        assert!((blend(100.0, 200.0, BlendMode::Mix, 1.0) - 200.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn overlay_darkens_shadows_and_brightens_highlights() {
+        // base oscura: 2·a·b; base clara: 1 - 2·(1 - a)·(1 - b)
+        assert!((blend(63.75, 127.5, BlendMode::Overlay, 1.0) - 63.75).abs() < 1e-3);
+        assert!((blend(191.25, 127.5, BlendMode::Overlay, 1.0) - 191.25).abs() < 1e-3);
+        assert!(blend(63.75, 63.75, BlendMode::Overlay, 1.0) < 63.75);
+        assert!(blend(191.25, 191.25, BlendMode::Overlay, 1.0) > 191.25);
+    }
+
+    #[test]
+    fn zero_factor_keeps_only_the_base() {
+        for mode in [BlendMode::Multiply, BlendMode::Add, BlendMode::Mix, BlendMode::Overlay] {
+            assert_eq!(blend(80.0, 200.0, mode, 0.0), 80.0);
+        }
+    }
+
+    #[test]
+    fn blend_nodes_nest() {
+        let inner = Texture::combine(flat(100.0), flat(50.0), BlendMode::Add, 1.0);
+        let outer = Texture::combine(inner, flat(127.5), BlendMode::Multiply, 1.0);
+        assert!((outer.sample(0.5, 0.5).r - 75.0).abs() < 1e-3);
+    }
+
+    fn stripes(rotation: f32) -> Texture {
+        Texture::Stripes { color1: Color::new(255.0, 0.0, 0.0), color2: Color::black(), scale: 4.0, axis: Axis::U, rotation }
+    }
+
+    // A 45° la frontera entre rayas es la diagonal u = v: el color se mantiene
+    // a lo largo de ella y cambia al cruzarla
+    #[test]
+    fn stripes_rotated_45_degrees_are_diagonal() {
+        let tex = stripes(std::f32::consts::FRAC_PI_4);
+        for t in [0.1, 0.3, 0.5, 0.7, 0.9] {
+            assert_eq!(tex.sample(t + 0.01, t).r, 255.0, "t = {t}");
+            assert_eq!(tex.sample(t - 0.01, t).r, 0.0, "t = {t}");
+        }
+    }
+
+    #[test]
+    fn unrotated_stripes_follow_the_axis() {
+        let tex = stripes(0.0);
+        // solo cuenta u: misma raya en toda la columna
+        for v in [0.1, 0.5, 0.9] {
+            assert_eq!(tex.sample(0.1, v).r, 255.0);
+            assert_eq!(tex.sample(0.3, v).r, 0.0);
+        }
+    }
+}
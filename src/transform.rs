@@ -0,0 +1,204 @@
+use nalgebra_glm::{self as glm, Mat4, Vec3};
+use std::sync::Arc;
+use crate::aabb::Aabb;
+use crate::light::{Light, LightKind, Spot};
+use crate::material::Material;
+use crate::ray_intersect::{RayIntersect, Intersect, PrimitiveKind};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+    pub matrix: Mat4,
+    pub inverse: Mat4,
+}
+
+impl Transform {
+    // Orden de aplicación: escala, rotación (euler X, Y, Z en radianes) y traslación
+    pub fn new(translation: Vec3, rotation: Vec3, scale: Vec3) -> Self {
+        let matrix = glm::translation(&translation)
+            * glm::rotation(rotation.z, &Vec3::z_axis())
+            * glm::rotation(rotation.y, &Vec3::y_axis())
+            * glm::rotation(rotation.x, &Vec3::x_axis())
+            * glm::scaling(&scale);
+        Transform::from_matrix(matrix)
+    }
+
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        // Una matriz singular (escala 0) deja el objeto sin intersecciones
+        let inverse = matrix.try_inverse().unwrap_or_else(Mat4::zeros);
+        Transform { matrix, inverse }
+    }
+
+    pub fn identity() -> Self {
+        Transform::from_matrix(Mat4::identity())
+    }
+
+    pub fn point_to_local(&self, p: &Vec3) -> Vec3 {
+        (self.inverse * glm::vec4(p.x, p.y, p.z, 1.0)).xyz()
+    }
+
+    pub fn dir_to_local(&self, d: &Vec3) -> Vec3 {
+        (self.inverse * glm::vec4(d.x, d.y, d.z, 0.0)).xyz()
+    }
+
+    pub fn point_to_world(&self, p: &Vec3) -> Vec3 {
+        (self.matrix * glm::vec4(p.x, p.y, p.z, 1.0)).xyz()
+    }
+
+    pub fn dir_to_world(&self, d: &Vec3) -> Vec3 {
+        (self.matrix * glm::vec4(d.x, d.y, d.z, 0.0)).xyz()
+    }
+
+    // Las normales se transforman con la inversa transpuesta
+    pub fn normal_to_world(&self, n: &Vec3) -> Vec3 {
+        (self.inverse.transpose() * glm::vec4(n.x, n.y, n.z, 0.0)).xyz().normalize()
+    }
+
+    // Caja en mundo que contiene la caja local transformada
+    pub fn transform_aabb(&self, local: &Aabb) -> Aabb {
+        if !local.is_finite() {
+            return *local;
+        }
+        let corners = local.corners().map(|c| self.point_to_world(&c));
+        Aabb::from_points(&corners)
+    }
+
+    pub fn transform_light(&self, light: &Light) -> Light {
+        let kind = match &light.kind {
+            LightKind::Point { radius } => LightKind::Point { radius: *radius },
+            LightKind::Directional { direction } => LightKind::Directional { direction: self.dir_to_world(direction).normalize() },
+            LightKind::Spot(spot) => LightKind::Spot(Spot {
+                direction: self.dir_to_world(&spot.direction).normalize(),
+                ..spot.clone()
+            }),
+            LightKind::Rect { u_edge, v_edge } => LightKind::Rect {
+                u_edge: self.dir_to_world(u_edge),
+                v_edge: self.dir_to_world(v_edge),
+            },
+        };
+        Light {
+            position: self.point_to_world(&light.position),
+            kind,
+            ..light.clone()
+        }
+    }
+
+    // Lleva el rayo al espacio local del objeto y el impacto de vuelta al mundo
+    pub fn intersect<'a, T: RayIntersect + ?Sized>(&self, object: &'a T, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect<'a> {
+        let local_origin = self.point_to_local(ray_origin);
+        let local_dir = self.dir_to_local(ray_direction);
+
+        // Los primitivos esperan direcciones unitarias; la escala se corrige en la distancia
+        let len = local_dir.magnitude();
+        if len == 0.0 {
+            return Intersect::empty();
+        }
+
+        let hit = object.ray_intersect(&local_origin, &(local_dir / len));
+        if !hit.is_intersecting {
+            return hit;
+        }
+
+        let t = hit.distance / len;
+        Intersect {
+            point: ray_origin + ray_direction * t,
+            normal: self.normal_to_world(&hit.normal),
+            tangent: hit.tangent.map(|tg| self.dir_to_world(&tg).normalize()),
+            distance: t,
+            ..hit
+        }
+    }
+}
+
+pub struct Transformed<T: RayIntersect> {
+    pub object: T,
+    pub transform: Transform,
+}
+
+impl<T: RayIntersect> Transformed<T> {
+    pub fn new(object: T, transform: Transform) -> Self {
+        Transformed { object, transform }
+    }
+}
+
+impl<T: RayIntersect> RayIntersect for Transformed<T> {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect<'_> {
+        self.transform.intersect(&self.object, ray_origin, ray_direction)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.transform.transform_aabb(&self.object.bounding_box())
+    }
+
+    fn is_light(&self) -> bool {
+        self.object.is_light()
+    }
+
+    fn casts_shadows(&self) -> bool {
+        self.object.casts_shadows()
+    }
+
+    fn emissive_lights(&self) -> Vec<Light> {
+        self.object.emissive_lights().iter().map(|l| self.transform.transform_light(l)).collect()
+    }
+
+    fn kind(&self) -> PrimitiveKind {
+        self.object.kind()
+    }
+
+    // La distancia a la arista se mide en local; el desplazamiento hasta ella
+    // se lleva al mundo, así la escala también cambia la distancia
+    fn edge_distance(&self, point: &Vec3) -> Option<(f32, Vec3)> {
+        let (distance, direction) = self.object.edge_distance(&self.transform.point_to_local(point))?;
+        let offset = self.transform.dir_to_world(&(direction * distance));
+        let world_direction = self.transform.dir_to_world(&direction).try_normalize(f32::EPSILON)?;
+        Some((offset.magnitude(), world_direction))
+    }
+
+    fn material(&self) -> Material {
+        self.object.material()
+    }
+}
+
+// Instancia de un objeto compartido: la geometría vive una sola vez en el `Arc`
+// y cada instancia solo guarda su transformación
+pub type Instance<T> = Transformed<Arc<T>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::Cube;
+    use crate::sphere::Sphere;
+
+    #[test]
+    fn rotated_cube_normal_follows_rotation() {
+        let angle = 30f32.to_radians();
+        let cube = Transformed::new(
+            Cube::new(Vec3::zeros(), 2.0, Material::black()),
+            Transform::new(Vec3::zeros(), Vec3::new(0.0, angle, 0.0), Vec3::repeat(1.0)),
+        );
+        let hit = cube.ray_intersect(&Vec3::new(0.0, 0.0, 5.0), &-Vec3::z());
+        assert!(hit.is_intersecting);
+        assert!((hit.normal - Vec3::new(angle.sin(), 0.0, angle.cos())).magnitude() < 1e-5, "{:?}", hit.normal);
+        // plano de la cara girada: n·p = 1
+        assert!((hit.distance - (5.0 - 1.0 / angle.cos())).abs() < 1e-4);
+    }
+
+    // Con escala no uniforme la normal usa la inversa transpuesta: en el
+    // ecuador de una esfera aplastada en Y sigue siendo horizontal y fuera de
+    // él es la del elipsoide, no la de la esfera escalada sin más
+    #[test]
+    fn scaled_sphere_normal_uses_inverse_transpose() {
+        let sphere = Transformed::new(
+            Sphere::new(Vec3::zeros(), 1.0, Material::black()),
+            Transform::new(Vec3::zeros(), Vec3::zeros(), Vec3::new(1.0, 0.5, 1.0)),
+        );
+        let equator = sphere.ray_intersect(&Vec3::new(0.0, 0.0, 5.0), &-Vec3::z());
+        assert!((equator.normal - Vec3::z()).magnitude() < 1e-5);
+        assert!((equator.distance - 4.0).abs() < 1e-5);
+
+        let hit = sphere.ray_intersect(&Vec3::new(0.0, 0.25, 5.0), &-Vec3::z());
+        // gradiente de x² + (y / 0.5)² + z² = 1
+        let expected = Vec3::new(hit.point.x, 4.0 * hit.point.y, hit.point.z).normalize();
+        assert!((hit.normal - expected).magnitude() < 1e-4, "{:?} vs {expected:?}", hit.normal);
+    }
+}
@@ -0,0 +1,184 @@
+use nalgebra_glm::Vec3;
+use crate::aabb::Aabb;
+use crate::color::Color;
+use crate::ray_intersect::{RayIntersect, Intersect, PrimitiveKind};
+use crate::material::Material;
+use std::borrow::Cow;
+
+pub struct Triangle {
+    pub v0: Vec3,
+    pub v1: Vec3,
+    pub v2: Vec3,
+    // Normales por vértice para sombreado suave; sin ellas se usa la normal geométrica
+    pub normals: Option<[Vec3; 3]>,
+    // Colores por vértice (filtro 0-255 sobre el difuso del material), para
+    // mapas de calor y datos por vértice; sin ellos se usa el difuso tal cual.
+    // Una textura en el material sigue reemplazando al difuso
+    pub colors: Option<[Color; 3]>,
+    pub material: Material,
+}
+
+impl Triangle {
+    pub fn new(v0: Vec3, v1: Vec3, v2: Vec3, material: Material) -> Self {
+        Triangle { v0, v1, v2, normals: None, colors: None, material }
+    }
+
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn with_colors(mut self, colors: [Color; 3]) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+}
+
+impl RayIntersect for Triangle {
+    // Möller-Trumbore
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect<'_> {
+        let e1 = self.v1 - self.v0;
+        let e2 = self.v2 - self.v0;
+        let p = ray_direction.cross(&e2);
+        let det = e1.dot(&p);
+        if det.abs() < 1e-8 {
+            return Intersect::empty();
+        }
+
+        let inv_det = 1.0 / det;
+        let s = ray_origin - self.v0;
+        let u = s.dot(&p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return Intersect::empty();
+        }
+
+        let q = s.cross(&e1);
+        let v = ray_direction.dot(&q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return Intersect::empty();
+        }
+
+        let t = e2.dot(&q) * inv_det;
+        if t <= 0.0 {
+            return Intersect::empty();
+        }
+
+        // interpolación baricéntrica de las normales de vértice
+        let w = 1.0 - u - v;
+        let normal = match self.normals {
+            Some([n0, n1, n2]) => (n0 * w + n1 * u + n2 * v).normalize(),
+            None => e1.cross(&e2).normalize(),
+        };
+
+        // con colores por vértice el material del impacto es una copia teñida
+        let material = match self.colors {
+            Some([c0, c1, c2]) => Cow::Owned(Material {
+                diffuse: self.material.diffuse.modulate(&(c0 * w + c1 * u + c2 * v)),
+                ..self.material.clone()
+            }),
+            None => Cow::Borrowed(&self.material),
+        };
+
+        let point = ray_origin + ray_direction * t;
+        Intersect::new(point, normal, t, material)
+            .with_uv(u, v)
+            .with_tangent(e1.normalize())
+            .with_face(ray_direction)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::from_points(&[self.v0, self.v1, self.v2])
+    }
+
+    fn kind(&self) -> PrimitiveKind {
+        PrimitiveKind::Triangle
+    }
+
+    fn material(&self) -> Material {
+        self.material.clone()
+    }
+}
+
+// Conjunto de triángulos tratado como un único objeto
+pub struct Mesh {
+    pub triangles: Vec<Triangle>,
+}
+
+impl Mesh {
+    pub fn new(triangles: Vec<Triangle>) -> Self {
+        Mesh { triangles }
+    }
+}
+
+impl RayIntersect for Mesh {
+    fn ray_intersect(&self, ray_origin: &Vec3, ray_direction: &Vec3) -> Intersect<'_> {
+        let mut closest = Intersect::empty();
+        let mut z = f32::INFINITY;
+        for tri in &self.triangles {
+            let i = tri.ray_intersect(ray_origin, ray_direction);
+            if i.is_intersecting && i.distance < z {
+                z = i.distance;
+                closest = i;
+            }
+        }
+        closest
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.triangles
+            .iter()
+            .fold(Aabb::empty(), |acc, tri| acc.union(&tri.bounding_box()))
+    }
+
+    fn kind(&self) -> PrimitiveKind {
+        PrimitiveKind::Mesh
+    }
+
+    // Se toma el material del primer triángulo
+    fn material(&self) -> Material {
+        self.triangles.first().map_or(Material::black(), |tri| tri.material.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tejado de dos triángulos que comparten la arista x = 0, z = 0 (la cumbrera)
+    fn roof(smooth: bool) -> Mesh {
+        let (bottom, top) = (Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let (left, right) = (Vec3::new(-1.0, 0.0, -1.0), Vec3::new(1.0, 0.0, -1.0));
+        let left_n = Vec3::new(-1.0, 0.0, 1.0).normalize();
+        let right_n = Vec3::new(1.0, 0.0, 1.0).normalize();
+        let ridge_n = Vec3::z();
+        let mut a = Triangle::new(bottom, top, left, Material::black());
+        let mut b = Triangle::new(bottom, right, top, Material::black());
+        if smooth {
+            a = a.with_normals([ridge_n, ridge_n, left_n]);
+            b = b.with_normals([ridge_n, right_n, ridge_n]);
+        }
+        Mesh::new(vec![a, b])
+    }
+
+    // Normales a ambos lados de la cumbrera, mirando desde +Z
+    fn normals_across_ridge(mesh: &Mesh) -> (Vec3, Vec3) {
+        let hit = |x: f32| mesh.ray_intersect(&Vec3::new(x, 0.0, 5.0), &-Vec3::z());
+        let (l, r) = (hit(-0.01), hit(0.01));
+        assert!(l.is_intersecting && r.is_intersecting);
+        (l.normal, r.normal)
+    }
+
+    #[test]
+    fn flat_normals_jump_at_shared_edge() {
+        let (l, r) = normals_across_ridge(&roof(false));
+        assert!((l - Vec3::new(-1.0, 0.0, 1.0).normalize()).magnitude() < 1e-5);
+        assert!((r - Vec3::new(1.0, 0.0, 1.0).normalize()).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn smooth_normals_are_continuous_at_shared_edge() {
+        let (l, r) = normals_across_ridge(&roof(true));
+        assert!((l - r).magnitude() < 0.05, "{l:?} vs {r:?}");
+        assert!(l.z > 0.99 && l.x < 0.0 && r.x > 0.0);
+    }
+}